@@ -0,0 +1,184 @@
+//! Audit log of tunnel usage: one JSON object per line for every accepted local connection
+//! and every forwarded-tcpip connection from the server, rotated by size.
+//!
+//! The log is process-wide, like the tracing subscriber: [`init`] is called once at service
+//! start and [`record`] is a no-op until then. Lines are written from a dedicated thread so
+//! relays never wait on the disk.
+
+use crate::config::AuditConfig;
+use crate::error::{AppError, Result};
+use crate::logging::{rfc3339, RotatingFile, Rotation};
+use crate::metrics::RelayRecord;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tracing::warn;
+
+static SINK: OnceLock<mpsc::Sender<AuditEntry>> = OnceLock::new();
+
+/// How a connection ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// Relayed until both sides closed
+    Closed,
+    /// Relayed, then ended by an I/O error (see `error`)
+    Error,
+    /// No SSH session came up within the reconnect grace period
+    NoSession,
+    /// The server refused or failed to open the direct-tcpip channel
+    OpenFailed,
+    /// Forwarded-tcpip: the local target could not be reached
+    LocalConnectFailed,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// When the connection ended (RFC 3339, UTC)
+    pub timestamp: String,
+    pub channel: String,
+    /// Channel type: direct-tcpip, forwarded-tcpip or dynamic
+    #[serde(rename = "type")]
+    pub channel_type: &'static str,
+    /// Local client address, or the originator the server reported for forwarded-tcpip
+    pub peer: String,
+    pub destination: String,
+    pub duration_ms: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// Entry for a relay that ran, from its final record
+    pub fn relay(channel: &str, channel_type: &'static str, record: &RelayRecord) -> Self {
+        let (outcome, error) = match record.close_reason.as_deref() {
+            None | Some("closed") => (Outcome::Closed, None),
+            Some(reason) => (Outcome::Error, Some(reason.to_string())),
+        };
+        Self {
+            timestamp: rfc3339(SystemTime::now()),
+            channel: channel.to_string(),
+            channel_type,
+            peer: record.peer.clone(),
+            destination: record.destination.clone(),
+            duration_ms: record.duration_ms,
+            bytes_sent: record.bytes_sent,
+            bytes_received: record.bytes_received,
+            outcome,
+            error,
+        }
+    }
+
+    /// Entry for a connection that was accepted but never relayed
+    pub fn refused(
+        channel: &str,
+        channel_type: &'static str,
+        peer: String,
+        destination: String,
+        outcome: Outcome,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            timestamp: rfc3339(SystemTime::now()),
+            channel: channel.to_string(),
+            channel_type,
+            peer,
+            destination,
+            duration_ms: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            outcome,
+            error,
+        }
+    }
+}
+
+/// Open the audit log and start its writer thread. Relative paths are resolved against
+/// `base_dir` (the config file's directory). Fails if the file cannot be opened.
+pub fn init(config: &AuditConfig, base_dir: &Path) -> Result<()> {
+    let path = crate::logging::resolve_path(&config.path, base_dir)?;
+    let rotation = match config.max_size_mb {
+        0 => Rotation::Never,
+        mb => Rotation::Size(mb.saturating_mul(1024 * 1024)),
+    };
+    let mut file = RotatingFile::open(path.clone(), rotation, config.max_files).map_err(|e| {
+        AppError::Config(format!("Cannot open audit log {}: {}", path.display(), e))
+    })?;
+
+    let (tx, rx) = mpsc::channel::<AuditEntry>();
+    if SINK.set(tx).is_err() {
+        return Err(AppError::Service(
+            "Audit log already initialized".to_string(),
+        ));
+    }
+    std::thread::Builder::new()
+        .name("audit-log".to_string())
+        .spawn(move || {
+            for entry in rx {
+                if let Err(e) = write_entry(&mut file, &entry) {
+                    warn!(path = %file.path().display(), error = %e, "Failed to write audit log");
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Append `entry` to the audit log, if one is configured
+pub fn record(entry: AuditEntry) {
+    if let Some(sink) = SINK.get() {
+        let _ = sink.send(entry);
+    }
+}
+
+fn write_entry(file: &mut RotatingFile, entry: &AuditEntry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    file.write_all(&line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(peer: &str) -> AuditEntry {
+        AuditEntry::refused(
+            "web",
+            "direct-tcpip",
+            peer.to_string(),
+            "localhost:80".to_string(),
+            Outcome::NoSession,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_entry_json() {
+        let record = RelayRecord {
+            id: 1,
+            peer: "127.0.0.1:50000".to_string(),
+            destination: "db:5432".to_string(),
+            started_at: 0,
+            ended_at: Some(2),
+            duration_ms: 2000,
+            bytes_sent: 10,
+            bytes_received: 20,
+            close_reason: Some("connection reset".to_string()),
+        };
+        let json = serde_json::to_value(AuditEntry::relay("db", "direct-tcpip", &record)).unwrap();
+        assert_eq!(json["type"], "direct-tcpip");
+        assert_eq!(json["outcome"], "error");
+        assert_eq!(json["error"], "connection reset");
+        assert_eq!(json["bytes_received"], 20);
+
+        let json = serde_json::to_value(entry("127.0.0.1:1")).unwrap();
+        assert_eq!(json["outcome"], "no-session");
+        assert!(json.get("error").is_none());
+    }
+}
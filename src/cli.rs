@@ -1,3 +1,4 @@
+use crate::config_format::ConfigFormat;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -32,10 +33,32 @@ pub enum Commands {
     Restart,
     /// Show service status
     Status,
+    /// List the connections a channel of the running service is relaying, with its totals
+    Connections {
+        /// Channel name
+        channel: String,
+    },
+    /// Stream the running service's events (channel state changes, relays) as JSON lines
+    Events,
+    /// Print the service log file (`[logging] file`), optionally following it
+    Logs {
+        /// Keep printing lines as they are written (survives rotation)
+        #[arg(short, long)]
+        follow: bool,
+        /// Only show lines logged for this channel
+        #[arg(long)]
+        channel: Option<String>,
+        /// Number of trailing lines to print first
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
     /// Validate configuration file
     Validate {
         /// Configuration file to validate
         config: Option<PathBuf>,
+        /// Print the configuration after includes and ${VAR} expansion (secrets redacted)
+        #[arg(long)]
+        show_resolved: bool,
     },
     /// Generate configuration from SSH config file
     Generate {
@@ -45,11 +68,50 @@ pub enum Commands {
         /// Output TOML config file path
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Only import host aliases matching these patterns (comma-separated, `*`/`?` wildcards, `!` negates)
+        #[arg(long)]
+        hosts: Option<String>,
+        /// Merge into the existing output file (keeps channels, comments and formatting) instead of overwriting
+        #[arg(long)]
+        merge: bool,
+        /// Output format (default: from the output file extension, else toml)
+        #[arg(long, value_enum)]
+        format: Option<ConfigFormat>,
+    },
+    /// Convert a configuration file between TOML, YAML and JSON
+    Convert {
+        /// Configuration file to convert (format taken from its extension)
+        input: PathBuf,
+        /// Output file path (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Target format (default: from the output file extension)
+        #[arg(long, value_enum)]
+        format: Option<ConfigFormat>,
+    },
+    /// Upgrade a configuration file to the current layout version (keeps a .bak copy)
+    Migrate {
+        /// Configuration file to migrate
+        config: Option<PathBuf>,
+        /// Print the migrated configuration instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the JSON Schema of the configuration file (for taplo / VS Code)
+    Schema {
+        /// Write the schema to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
-    /// Test if channels are actually working by connecting to local ports
+    /// Test channels of the running service end to end (exit code: 0 ok, 2 failed, 3 down, 4 service not running)
     Test {
+        /// Only test this channel
+        channel: Option<String>,
         /// Configuration file path
         #[arg(short, long)]
         config: Option<PathBuf>,
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
     },
 }
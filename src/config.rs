@@ -1,9 +1,18 @@
+use crate::config_format::ConfigFormat;
 use crate::error::{AppError, Result};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+    InstanceType, Metadata, RootSchema, Schema, SchemaObject, StringValidation,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
 
 /// SSH host definition (previously channel definition)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct HostConfig {
     /// Host name/identifier (used by channels to reference)
     pub name: String,
@@ -16,26 +25,50 @@ pub struct HostConfig {
     pub username: String,
     /// Authentication method
     pub auth: AuthConfig,
+    /// Send an SSH keepalive after this many seconds without traffic (default 15, 0 disables)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive_interval_secs: Option<u64>,
+    /// Treat the session as dead after this many unanswered keepalives (default 3, 0 = never)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive_max: Option<usize>,
+    /// Reconnection settings for channels of this host (override `[reconnection]` field by field)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnection: Option<ReconnectionOverride>,
 }
 
 fn default_ssh_port() -> u16 {
     22
 }
 
+/// Keepalive defaults for hosts that don't set `keepalive_interval_secs` / `keepalive_max`
+pub const DEFAULT_KEEPALIVE_INTERVAL_SECS: u64 = 15;
+pub const DEFAULT_KEEPALIVE_MAX: usize = 3;
+
 /// Port forwarding configuration (local:dest format)
 #[derive(Debug, Clone)]
 pub struct PortForward {
     /// Local port to bind (required)
     pub local_port: Option<u16>,
-    /// Destination port (required)
+    /// Destination port (required, except for dynamic channels where it is 0)
     pub dest_port: u16,
 }
 
 impl PortForward {
     /// Parse port forward string in format "local:dest"
-    /// Both local and dest ports are required (e.g., "80:3923")
+    /// Both local and dest ports are required (e.g., "80:3923").
+    /// A single port (e.g., "1080") is accepted for dynamic channels and leaves dest_port at 0.
     fn parse(s: &str) -> Result<Self> {
         let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() == 1 && !parts[0].is_empty() {
+            let local_port = parts[0].parse::<u16>().map_err(|e| {
+                AppError::Config(format!("Invalid local port '{}': {}", parts[0], e))
+            })?;
+            return Ok(PortForward {
+                local_port: Some(local_port),
+                dest_port: 0,
+            });
+        }
+
         if parts.len() != 2 {
             return Err(AppError::Config(format!(
                 "Invalid port format '{}'. Expected format: 'local:dest' (e.g., '80:3923')",
@@ -77,8 +110,59 @@ impl<'de> Deserialize<'de> for PortForward {
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        PortForward::parse(&s).map_err(serde::de::Error::custom)
+        deserializer.deserialize_any(PortForwardVisitor)
+    }
+}
+
+/// Accepts the ports string, or a bare integer (YAML/JSON `ports: 1080`) for dynamic channels
+struct PortForwardVisitor;
+
+impl serde::de::Visitor<'_> for PortForwardVisitor {
+    type Value = PortForward;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a port string such as \"8080:80\" or a single port")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<PortForward, E> {
+        PortForward::parse(s).map_err(E::custom)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, port: u64) -> std::result::Result<PortForward, E> {
+        PortForward::parse(&port.to_string()).map_err(E::custom)
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, port: i64) -> std::result::Result<PortForward, E> {
+        PortForward::parse(&port.to_string()).map_err(E::custom)
+    }
+}
+
+/// Grammar of the `ports` string: "local:dest", "remote:local" or a single port
+const PORTS_PATTERN: &str = "^[0-9]{1,5}(:[0-9]{1,5})?$";
+
+impl JsonSchema for PortForward {
+    fn schema_name() -> String {
+        "PortForward".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(vec![InstanceType::String, InstanceType::Integer].into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(PORTS_PATTERN.to_string()),
+                ..Default::default()
+            })),
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "direct-tcpip: \"local:dest\"; forwarded-tcpip: \"remote:local\"; dynamic: \"local\""
+                        .to_string(),
+                ),
+                examples: vec!["8080:80".into(), "8022:80".into(), "1080".into()],
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
     }
 }
 
@@ -88,35 +172,467 @@ impl Serialize for PortForward {
         S: Serializer,
     {
         let local = self.local_port.expect("local_port must be set");
-        let s = format!("{}:{}", local, self.dest_port);
+        let s = if self.dest_port == 0 {
+            local.to_string()
+        } else {
+            format!("{}:{}", local, self.dest_port)
+        };
         serializer.serialize_str(&s)
     }
 }
 
+/// A channel's `hostname`: one host name, or failover candidates in order of preference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hostnames(Vec<String>);
+
+impl Hostnames {
+    /// Preferred (first) host
+    pub fn primary(&self) -> &str {
+        &self.0[0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
+    /// Whether fallback hosts follow the preferred one
+    pub fn has_fallbacks(&self) -> bool {
+        self.0.len() > 1
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.iter().any(|h| h == name)
+    }
+}
+
+impl From<String> for Hostnames {
+    fn from(name: String) -> Self {
+        Hostnames(vec![name])
+    }
+}
+
+impl From<&str> for Hostnames {
+    fn from(name: &str) -> Self {
+        Hostnames(vec![name.to_string()])
+    }
+}
+
+impl PartialEq<String> for Hostnames {
+    fn eq(&self, other: &String) -> bool {
+        self.0.len() == 1 && self.0[0] == *other
+    }
+}
+
+impl std::fmt::Display for Hostnames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0.join(", "))
+    }
+}
+
+impl<'de> Deserialize<'de> for Hostnames {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(HostnamesVisitor)
+    }
+}
+
+/// Accepts a host name or a non-empty list of host names
+struct HostnamesVisitor;
+
+impl<'de> serde::de::Visitor<'de> for HostnamesVisitor {
+    type Value = Hostnames;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a host name or a list of host names")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> std::result::Result<Hostnames, E> {
+        Ok(Hostnames::from(s))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Hostnames, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut names = Vec::new();
+        while let Some(name) = seq.next_element::<String>()? {
+            names.push(name);
+        }
+        if names.is_empty() {
+            return Err(serde::de::Error::custom("hostname list must not be empty"));
+        }
+        Ok(Hostnames(names))
+    }
+}
+
+impl Serialize for Hostnames {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0.as_slice() {
+            [one] => serializer.serialize_str(one),
+            many => many.serialize(serializer),
+        }
+    }
+}
+
+impl JsonSchema for Hostnames {
+    fn schema_name() -> String {
+        "Hostnames".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let list = SchemaObject {
+            instance_type: Some(InstanceType::Array.into()),
+            array: Some(Box::new(schemars::schema::ArrayValidation {
+                items: Some(gen.subschema_for::<String>().into()),
+                min_items: Some(1),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(schemars::schema::SubschemaValidation {
+                any_of: Some(vec![gen.subschema_for::<String>(), list.into()]),
+                ..Default::default()
+            })),
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "Host name, or a list tried in order (failover to the next on connection failure)"
+                        .to_string(),
+                ),
+                examples: vec!["bastion".into(), vec!["bastion-eu", "bastion-us"].into()],
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 /// Channel definition referencing a host
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ConnectionConfig {
     /// Channel name/identifier
     pub name: String,
-    /// Host reference (must match hosts.name)
-    pub hostname: String,
-    /// Channel type: "direct-tcpip" (local forward, like ssh -L) or "forwarded-tcpip" (remote forward, like ssh -R).
+    /// Host reference (must match hosts.name); a list means failover candidates, preferred first
+    pub hostname: Hostnames,
+    /// Channel type: "direct-tcpip" (local forward, like ssh -L), "forwarded-tcpip" (remote forward, like ssh -R)
+    /// or "dynamic" (local SOCKS5 proxy, like ssh -D).
     /// Default: "direct-tcpip"
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "channel_type_schema")]
     pub channel_type: Option<String>,
     /// Port forwarding configuration.
     /// For direct-tcpip: "local:dest" (local listen port : remote dest port). Example: "80:3923"
     /// For forwarded-tcpip: "remote:local" (remote bind port : local connect port). Example: "8022:80"
+    /// For dynamic: "local" (SOCKS listen port). Example: "1080"
     pub ports: PortForward,
     /// For direct-tcpip: destination host on remote (defaults to 127.0.0.1).
     /// For forwarded-tcpip: local host to connect to (defaults to 127.0.0.1).
     #[serde(default = "default_destination_host")]
     pub dest_host: String,
-    /// Local listen address for direct-tcpip (defaults to 127.0.0.1).
+    /// Local listen address for direct-tcpip and dynamic (defaults to 127.0.0.1).
     /// Use "0.0.0.0" to accept connections from any interface.
     /// Ignored for forwarded-tcpip.
     #[serde(default = "default_listen_host")]
     pub listen_host: String,
+    /// Reconnection settings for this channel (override the host's and `[reconnection]` field by field)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnection: Option<ReconnectionOverride>,
+    /// For direct-tcpip and dynamic: number of SSH sessions kept open in parallel, with local
+    /// clients spread across them (default 1). With several `hostname` candidates, session N
+    /// prefers the Nth host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<u32>,
+    /// How local clients are spread across `sessions` (default "round-robin")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<BalanceStrategy>,
+    /// Shell command run when the channel comes up (first session connected); overrides
+    /// `[hooks] on_up`, and "" turns it off for this channel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_up: Option<String>,
+    /// Shell command run when the channel goes down (last session lost)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_down: Option<String>,
+    /// Shell command run when a connection attempt fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_fail: Option<String>,
+    /// Periodic application-level check of the destination through each SSH session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+/// `health_check`: how and how often a channel's destination is probed through the tunnel
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HealthCheckConfig {
+    #[serde(flatten)]
+    pub probe: HealthProbe,
+    /// "host:port" to probe, as seen from the SSH server. Required for dynamic channels;
+    /// others default to their destination (remote forwards: the port bound on the server,
+    /// which needs a probe other than tcp, since the server itself accepts that connection)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Seconds between checks (default 30)
+    #[serde(default = "default_health_interval_secs")]
+    pub interval_secs: u64,
+    /// Seconds a check may take before it counts as failed (default 5)
+    #[serde(default = "default_health_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Drop the session and reconnect after this many failed checks in a row (default 0: only report)
+    #[serde(default)]
+    pub reconnect_after: u32,
+}
+
+/// What a health check sends and expects, selected by `probe`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "probe", rename_all = "kebab-case")]
+pub enum HealthProbe {
+    /// The SSH server can open a TCP connection to the target
+    Tcp,
+    /// The target greets with a line starting with `expect` (e.g. "SSH-", "220")
+    Banner { expect: String },
+    /// `GET path` is answered with `expect_status`
+    Http {
+        #[serde(default = "default_health_http_path")]
+        path: String,
+        #[serde(default = "default_health_http_status")]
+        expect_status: u16,
+    },
+    /// PostgreSQL answers an SSLRequest
+    Postgres,
+    /// Redis answers PING
+    Redis,
+    /// MySQL / MariaDB sends its initial handshake
+    Mysql,
+}
+
+impl HealthProbe {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HealthProbe::Tcp => "tcp",
+            HealthProbe::Banner { .. } => "banner",
+            HealthProbe::Http { .. } => "http",
+            HealthProbe::Postgres => "postgres",
+            HealthProbe::Redis => "redis",
+            HealthProbe::Mysql => "mysql",
+        }
+    }
+}
+
+fn default_health_interval_secs() -> u64 {
+    30
+}
+
+fn default_health_timeout_secs() -> u64 {
+    5
+}
+
+fn default_health_http_path() -> String {
+    "/".to_string()
+}
+
+fn default_health_http_status() -> u16 {
+    200
+}
+
+/// Accepted `channel_type` values
+pub const CHANNEL_TYPES: [&str; 4] = ["direct-tcpip", "forwarded-tcpip", "dynamic", "session"];
+
+/// Why a `[[channels]]` entry cannot be used, and which of its keys is at fault
+/// (`validate` points at that key; `build_channels` turns it into a config error)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelError {
+    pub key: &'static str,
+    pub message: String,
+}
+
+impl ChannelError {
+    fn new(key: &'static str, message: String) -> Self {
+        Self { key, message }
+    }
+}
+
+impl From<ChannelError> for AppError {
+    fn from(e: ChannelError) -> Self {
+        AppError::Config(e.message)
+    }
+}
+
+impl ConnectionConfig {
+    /// `channel_type`, defaulting to direct-tcpip
+    pub fn channel_type(&self) -> &str {
+        self.channel_type.as_deref().unwrap_or("direct-tcpip")
+    }
+
+    /// Runtime parameters for the channel type, from `ports`, `dest_host` and `listen_host`
+    pub fn params(&self) -> std::result::Result<ChannelTypeParams, ChannelError> {
+        let ports_error = |form: &str| {
+            ChannelError::new(
+                "ports",
+                format!(
+                    "Channel '{}': {} requires ports {}",
+                    self.name,
+                    self.channel_type(),
+                    form
+                ),
+            )
+        };
+        match self.channel_type() {
+            "forwarded-tcpip" => match (self.ports.local_port, self.ports.dest_port) {
+                (Some(local_connect_port), remote_bind_port) if remote_bind_port != 0 => {
+                    Ok(ChannelTypeParams::ForwardedTcpIp {
+                        remote_bind_port,
+                        local_connect_host: self.dest_host.clone(),
+                        local_connect_port,
+                    })
+                }
+                _ => Err(ports_error("local:remote (e.g. 80:8022)")),
+            },
+            "session" => Ok(ChannelTypeParams::Session { command: None }),
+            "dynamic" => match self.ports.local_port {
+                Some(local_port) => Ok(ChannelTypeParams::Dynamic {
+                    listen_host: self.listen_host.clone(),
+                    local_port,
+                }),
+                None => Err(ports_error("local (e.g. 1080)")),
+            },
+            "direct-tcpip" => match (self.ports.local_port, self.ports.dest_port) {
+                (Some(local_port), dest_port) if dest_port != 0 => {
+                    Ok(ChannelTypeParams::DirectTcpIp {
+                        listen_host: self.listen_host.clone(),
+                        local_port,
+                        dest_host: self.dest_host.clone(),
+                        dest_port,
+                    })
+                }
+                _ => Err(ports_error("local:remote (e.g. 8080:80)")),
+            },
+            unknown => Err(ChannelError::new(
+                "channel_type",
+                format!(
+                    "Channel '{}': unknown channel_type '{}', expected 'direct-tcpip', 'forwarded-tcpip', 'dynamic', or 'session'",
+                    self.name, unknown
+                ),
+            )),
+        }
+    }
+
+    /// Number of parallel sessions; more than one only for channels with a local listener
+    pub fn session_count(
+        &self,
+        params: &ChannelTypeParams,
+    ) -> std::result::Result<usize, ChannelError> {
+        let sessions = self.sessions.unwrap_or(1) as usize;
+        let pooled = matches!(
+            params,
+            ChannelTypeParams::DirectTcpIp { .. } | ChannelTypeParams::Dynamic { .. }
+        );
+        if sessions == 0 {
+            return Err(ChannelError::new(
+                "sessions",
+                format!("Channel '{}': sessions must be at least 1", self.name),
+            ));
+        }
+        if sessions > 1 && !pooled {
+            return Err(ChannelError::new(
+                "sessions",
+                format!(
+                    "Channel '{}': sessions > 1 is only supported for direct-tcpip and dynamic channels",
+                    self.name
+                ),
+            ));
+        }
+        Ok(sessions)
+    }
+
+    /// Resolve `health_check` to the address it probes from the SSH server
+    pub fn health_check(
+        &self,
+        params: &ChannelTypeParams,
+    ) -> std::result::Result<Option<HealthCheck>, ChannelError> {
+        let Some(check) = &self.health_check else {
+            return Ok(None);
+        };
+        let invalid = |msg: String| {
+            ChannelError::new(
+                "health_check",
+                format!("Channel '{}': health_check {}", self.name, msg),
+            )
+        };
+        let (host, port) = match (&check.target, params) {
+            (_, ChannelTypeParams::Session { .. }) => {
+                return Err(invalid("is not supported on session channels".to_string()))
+            }
+            (Some(target), _) => {
+                let port = target
+                    .rsplit_once(':')
+                    .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+                    .filter(|(host, port)| !host.is_empty() && *port != 0);
+                let Some((host, port)) = port else {
+                    return Err(invalid(format!("target '{}' must be host:port", target)));
+                };
+                (host.to_string(), port)
+            }
+            (
+                None,
+                ChannelTypeParams::DirectTcpIp {
+                    dest_host,
+                    dest_port,
+                    ..
+                },
+            ) => (dest_host.clone(), *dest_port),
+            // The server accepts connections to its end of the remote forward by itself; only an
+            // answer from the service proves they reach the local target
+            (
+                None,
+                ChannelTypeParams::ForwardedTcpIp {
+                    remote_bind_port, ..
+                },
+            ) => {
+                if check.probe == HealthProbe::Tcp {
+                    return Err(invalid(
+                        "on forwarded-tcpip needs a probe that gets an answer from the local service (banner, http, postgres, redis or mysql); tcp only reaches the SSH server".to_string(),
+                    ));
+                }
+                ("127.0.0.1".to_string(), *remote_bind_port)
+            }
+            (None, ChannelTypeParams::Dynamic { .. }) => {
+                return Err(invalid(
+                    "needs a target (host:port) on dynamic channels".to_string(),
+                ))
+            }
+        };
+        if check.interval_secs == 0 || check.timeout_secs == 0 {
+            return Err(invalid(
+                "interval_secs and timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        Ok(Some(HealthCheck {
+            probe: check.probe.clone(),
+            host,
+            port,
+            interval: Duration::from_secs(check.interval_secs),
+            timeout: Duration::from_secs(check.timeout_secs),
+            reconnect_after: check.reconnect_after,
+        }))
+    }
+}
+
+fn channel_type_schema(_gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(CHANNEL_TYPES.iter().map(|t| (*t).into()).collect()),
+        metadata: Some(Box::new(Metadata {
+            default: Some("direct-tcpip".into()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
 }
 
 fn default_listen_host() -> String {
@@ -127,10 +643,19 @@ fn default_destination_host() -> String {
     "127.0.0.1".to_string()
 }
 
-/// SSH channel configuration (runtime)
+/// Map an OpenSSH forward bind address to a listen_host ("*" or empty means all interfaces)
+fn listen_host_from_bind_address(bind_address: Option<&str>) -> String {
+    match bind_address {
+        None | Some("localhost") => default_listen_host(),
+        Some("*") | Some("") => "0.0.0.0".to_string(),
+        Some(addr) => addr.to_string(),
+    }
+}
+
+/// SSH server a channel connects through (runtime, resolved from a `[[hosts]]` entry)
 #[derive(Debug, Clone)]
-pub struct ChannelConfig {
-    /// Channel name/identifier
+pub struct HostEndpoint {
+    /// Host name/identifier from `[[hosts]]`
     pub name: String,
     /// Remote host address
     pub host: String,
@@ -140,11 +665,79 @@ pub struct ChannelConfig {
     pub username: String,
     /// Authentication method
     pub auth: AuthConfig,
+    /// Keepalive interval for the SSH session (None = disabled)
+    pub keepalive_interval: Option<Duration>,
+    /// Unanswered keepalives before the session is considered dead (0 = never)
+    pub keepalive_max: usize,
+}
+
+impl HostEndpoint {
+    fn from_host(host: &HostConfig) -> Self {
+        Self {
+            name: host.name.clone(),
+            host: host.host.clone(),
+            port: host.port,
+            username: host.username.clone(),
+            auth: host.auth.clone(),
+            keepalive_interval: match host
+                .keepalive_interval_secs
+                .unwrap_or(DEFAULT_KEEPALIVE_INTERVAL_SECS)
+            {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            keepalive_max: host.keepalive_max.unwrap_or(DEFAULT_KEEPALIVE_MAX),
+        }
+    }
+}
+
+/// SSH channel configuration (runtime)
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    /// Channel name/identifier
+    pub name: String,
+    /// Hosts to connect through, preferred first (never empty)
+    pub hosts: Vec<HostEndpoint>,
     /// Channel type string for logging and status display (e.g. "direct-tcpip", "forwarded-tcpip")
-    #[allow(dead_code)]
     pub channel_type: String,
     /// Parameters specific to the channel type; semantics are explicit per variant
     pub params: ChannelTypeParams,
+    /// Effective reconnection settings (global, then host, then channel overrides)
+    pub reconnection: ReconnectionConfig,
+    /// Parallel SSH sessions (at least 1; more only for direct-tcpip and dynamic)
+    pub sessions: usize,
+    pub balance: BalanceStrategy,
+    /// Effective lifecycle hooks (channel's own, else `[hooks]`)
+    pub hooks: ChannelHooks,
+    pub health_check: Option<HealthCheck>,
+}
+
+/// Health check of a channel, resolved to the address each session probes
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    pub probe: HealthProbe,
+    /// Target as seen from the SSH server
+    pub host: String,
+    pub port: u16,
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// Failed checks in a row that force a reconnect (0 = never)
+    pub reconnect_after: u32,
+}
+
+/// Hook commands of a channel, resolved from the channel and `[hooks]`
+#[derive(Debug, Clone, Default)]
+pub struct ChannelHooks {
+    pub on_up: Option<String>,
+    pub on_down: Option<String>,
+    pub on_fail: Option<String>,
+    pub timeout: Duration,
+}
+
+impl ChannelHooks {
+    pub fn is_empty(&self) -> bool {
+        self.on_up.is_none() && self.on_down.is_none() && self.on_fail.is_none()
+    }
 }
 
 /// Parameters for each channel type. Makes intent explicit and type-safe.
@@ -163,12 +756,17 @@ pub enum ChannelTypeParams {
         local_connect_host: String,
         local_connect_port: u16,
     },
+    /// Dynamic forwarding (ssh -D): local SOCKS5 proxy, each CONNECT opens a direct-tcpip channel.
+    Dynamic {
+        listen_host: String,
+        local_port: u16,
+    },
     /// Session channel (e.g. shell or single command).
     Session { command: Option<String> },
 }
 
 /// Authentication configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum AuthConfig {
     /// Password authentication
@@ -180,14 +778,32 @@ pub enum AuthConfig {
         /// Path to private key file
         key_path: PathBuf,
         /// Optional passphrase for the key
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         passphrase: Option<String>,
     },
 }
 
+/// Prefix for channel `hostname` values that reference an ~/.ssh/config alias directly
+pub const SSH_CONFIG_HOST_PREFIX: &str = "ssh:";
+
 /// Application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppConfig {
+    /// Config layout version; older files are upgraded on load and by `migrate`
+    #[serde(default = "default_config_version")]
+    pub version: u32,
+    /// Additional config files (globs allowed, relative to this file) whose hosts and channels are merged in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Reusable values for `${NAME}` interpolation in hosts and channels (looked up before the environment)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    /// SSH config file used to resolve channel hosts not defined in `hosts`
+    /// (`hostname = "ssh:alias"` always resolves through it, default ~/.ssh/config)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_config: Option<PathBuf>,
     /// SSH hosts definition (replaces channels)
+    #[serde(default)]
     pub hosts: Vec<HostConfig>,
     /// Channels referencing hosts
     #[serde(default)]
@@ -195,10 +811,243 @@ pub struct AppConfig {
     /// Reconnection settings
     #[serde(default)]
     pub reconnection: ReconnectionConfig,
+    /// Prometheus `/metrics` endpoint (off unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsConfig>,
+    /// JSON-lines audit log of tunnel connections (off unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditConfig>,
+    /// Default lifecycle hook commands for all channels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HooksConfig>,
+    /// Service log file, level and format (stderr only unless `file` is set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingConfig>,
+    /// File each host/channel was loaded from (filled by `from_file`)
+    #[serde(skip)]
+    pub origins: ConfigOrigins,
+}
+
+/// `[metrics]`: serve per-channel counters for Prometheus
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MetricsConfig {
+    /// Address to serve `GET /metrics` on (default 127.0.0.1:9184)
+    #[serde(default = "default_metrics_listen")]
+    pub listen: String,
+}
+
+fn default_metrics_listen() -> String {
+    "127.0.0.1:9184".to_string()
+}
+
+/// `[audit]`: record every relayed connection as a JSON line
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditConfig {
+    /// Log file (relative paths are resolved against the config file's directory)
+    pub path: PathBuf,
+    /// Rotate once the file would grow past this many MiB (0 = never rotate)
+    #[serde(default = "default_audit_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files to keep as `<path>.1` … `<path>.N` (0 = truncate instead)
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+fn default_audit_max_size_mb() -> u64 {
+    10
+}
+
+fn default_audit_max_files() -> usize {
+    5
+}
+
+/// `[hooks]`: commands run on channel lifecycle events, unless a channel sets its own
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HooksConfig {
+    /// Shell command run when a channel comes up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_up: Option<String>,
+    /// Shell command run when a channel goes down
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_down: Option<String>,
+    /// Shell command run when a connection attempt fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_fail: Option<String>,
+    /// Kill a hook still running after this many seconds
+    #[serde(default = "default_hook_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            on_up: None,
+            on_down: None,
+            on_fail: None,
+            timeout_secs: default_hook_timeout_secs(),
+        }
+    }
+}
+
+fn default_hook_timeout_secs() -> u64 {
+    30
+}
+
+/// `[logging]`: service log destination and format
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LoggingConfig {
+    /// Also write the log to this file (relative paths are resolved against the config file's
+    /// directory); read it back with `ssh-channels-hub logs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Level or filter directives, e.g. "info" or "warn,ssh_channels_hub=debug"
+    /// (`--debug` and `RUST_LOG` take precedence)
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Line format of the log file
+    #[serde(default)]
+    pub format: LogFormat,
+    /// When to rotate the log file
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Size limit for `rotation = "size"`, in MiB
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files to keep as `<file>.1` … `<file>.N` (0 = truncate instead)
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            level: default_log_level(),
+            format: LogFormat::default(),
+            rotation: LogRotation::default(),
+            max_size_mb: default_log_max_size_mb(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+/// Log file line format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Human-readable lines, as on the console
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+/// When the log file is moved aside
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogRotation {
+    /// Once it would grow past `max_size_mb`
+    #[default]
+    Size,
+    /// At midnight UTC
+    Daily,
+    /// Never (rotate externally, e.g. with logrotate copytruncate)
+    Never,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+fn default_config_version() -> u32 {
+    crate::config_migrate::CONFIG_VERSION
+}
+
+/// Source file of each host and channel, keyed by name
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOrigins {
+    pub hosts: HashMap<String, PathBuf>,
+    pub channels: HashMap<String, PathBuf>,
+}
+
+/// Connections go straight to the host; say so when the SSH config routes it through a jump host
+fn warn_unsupported_jump(entry: &crate::ssh_config::SshConfigEntry) {
+    if let Some(jump) = entry.jump_host() {
+        warn!(
+            alias = %entry.host,
+            proxy_jump = %jump,
+            "ProxyJump is not supported; the host will be connected to directly"
+        );
+    }
+}
+
+/// Build a runtime host from a resolved SSH config entry, applying OpenSSH defaults:
+/// HostName defaults to the alias, User to the local user, and the identity to the first
+/// existing default key (~/.ssh/id_ed25519, id_ecdsa, id_rsa) unless `IdentitiesOnly yes`.
+fn host_from_ssh_config_entry(
+    name: String,
+    entry: crate::ssh_config::SshConfigEntry,
+) -> Result<HostConfig> {
+    warn_unsupported_jump(&entry);
+    if entry.identity_file.is_none() && entry.identities_only == Some(true) {
+        return Err(AppError::Config(format!(
+            "SSH config alias '{}' sets IdentitiesOnly yes but has no IdentityFile",
+            entry.host
+        )));
+    }
+    let key_path = entry
+        .identity_file
+        .or_else(|| {
+            let ssh_dir = dirs::home_dir()?.join(".ssh");
+            ["id_ed25519", "id_ecdsa", "id_rsa"]
+                .iter()
+                .map(|k| ssh_dir.join(k))
+                .find(|p| p.exists())
+        })
+        .ok_or_else(|| {
+            AppError::Config(format!(
+                "SSH config alias '{}' has no IdentityFile and no default key was found",
+                entry.host
+            ))
+        })?;
+
+    let username = match entry.user {
+        Some(u) => u,
+        None => std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .map_err(|_| {
+                AppError::Config(format!(
+                    "SSH config alias '{}' has no User and the local user is unknown",
+                    entry.host
+                ))
+            })?,
+    };
+
+    Ok(HostConfig {
+        name,
+        host: entry.hostname.unwrap_or_else(|| entry.host.clone()),
+        port: entry.port.unwrap_or(22),
+        username,
+        auth: AuthConfig::Key {
+            key_path,
+            passphrase: None,
+        },
+        keepalive_interval_secs: entry.server_alive_interval,
+        keepalive_max: entry.server_alive_count_max,
+        reconnection: None,
+    })
 }
 
 /// Reconnection configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ReconnectionConfig {
     /// Maximum retry attempts (0 = unlimited)
     #[serde(default = "default_max_retries")]
@@ -209,9 +1058,142 @@ pub struct ReconnectionConfig {
     /// Maximum delay in seconds between retries
     #[serde(default = "default_max_delay")]
     pub max_delay_secs: u64,
-    /// Use exponential backoff (true) or fixed interval (false)
+    /// Use exponential backoff (true) or fixed interval (false); ignored when `backoff` is set
     #[serde(default = "default_use_exponential")]
     pub use_exponential_backoff: bool,
+    /// Delay strategy: "fixed", "exponential" or "decorrelated-jitter" (default from `use_exponential_backoff`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<BackoffStrategy>,
+    /// A connection that stays up this long resets the retry count and delay
+    #[serde(default = "default_reset_after")]
+    pub reset_after_secs: u64,
+    /// How long local clients arriving during a reconnect wait for the session before being closed
+    #[serde(default = "default_client_grace")]
+    pub client_grace_secs: u64,
+    /// With several `hostname` candidates: while on a fallback host, probe the preferred one this
+    /// often and switch back once it is reachable (0 = stay on the fallback)
+    #[serde(default)]
+    pub failback_interval_secs: u64,
+    /// Add a random delay (up to `initial_delay_secs`) to each retry so channels don't reconnect in lockstep
+    #[serde(default)]
+    pub jitter: bool,
+    /// What to do when authentication is rejected: "retry", "backoff" (default) or "stop"
+    #[serde(default)]
+    pub auth_failure: AuthFailurePolicy,
+    /// Delay before retrying after an authentication failure when `auth_failure = "backoff"`
+    #[serde(default = "default_auth_failure_delay")]
+    pub auth_failure_delay_secs: u64,
+}
+
+/// How the delay between reconnection attempts grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackoffStrategy {
+    /// Always wait `initial_delay_secs`
+    Fixed,
+    /// Double the delay after each failure, up to `max_delay_secs`
+    Exponential,
+    /// Random delay between `initial_delay_secs` and three times the previous one, up to `max_delay_secs`
+    DecorrelatedJitter,
+}
+
+/// How a channel with several sessions picks one for each new local client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BalanceStrategy {
+    /// Take live sessions in turn
+    #[default]
+    RoundRobin,
+    /// Take the live session relaying the fewest connections
+    LeastConnections,
+}
+
+/// What to do when the server rejects authentication (bad password/key)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthFailurePolicy {
+    /// Retry like any other connection error
+    Retry,
+    /// Wait `auth_failure_delay_secs` before the next attempt
+    #[default]
+    Backoff,
+    /// Stop the channel until the service is restarted
+    Stop,
+}
+
+/// Per-host / per-channel reconnection settings; unset fields keep the enclosing level's value
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ReconnectionOverride {
+    /// Maximum retry attempts (0 = unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// Initial delay in seconds before retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_delay_secs: Option<u64>,
+    /// Maximum delay in seconds between retries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delay_secs: Option<u64>,
+    /// Use exponential backoff (true) or fixed interval (false); ignored when `backoff` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_exponential_backoff: Option<bool>,
+    /// Delay strategy: "fixed", "exponential" or "decorrelated-jitter"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<BackoffStrategy>,
+    /// A connection that stays up this long resets the retry count and delay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset_after_secs: Option<u64>,
+    /// How long local clients arriving during a reconnect wait for the session before being closed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_grace_secs: Option<u64>,
+    /// While on a fallback host, probe the preferred one this often (0 = stay on the fallback)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failback_interval_secs: Option<u64>,
+    /// Add a random delay (up to `initial_delay_secs`) to each retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<bool>,
+    /// What to do when authentication is rejected: "retry", "backoff" or "stop"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_failure: Option<AuthFailurePolicy>,
+    /// Delay before retrying after an authentication failure when `auth_failure = "backoff"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_failure_delay_secs: Option<u64>,
+}
+
+impl ReconnectionConfig {
+    /// Apply the fields set in `overrides` on top of these settings
+    pub fn with_override(&self, overrides: Option<&ReconnectionOverride>) -> Self {
+        let Some(o) = overrides else {
+            return self.clone();
+        };
+        Self {
+            max_retries: o.max_retries.unwrap_or(self.max_retries),
+            initial_delay_secs: o.initial_delay_secs.unwrap_or(self.initial_delay_secs),
+            max_delay_secs: o.max_delay_secs.unwrap_or(self.max_delay_secs),
+            use_exponential_backoff: o
+                .use_exponential_backoff
+                .unwrap_or(self.use_exponential_backoff),
+            backoff: o.backoff.or(self.backoff),
+            reset_after_secs: o.reset_after_secs.unwrap_or(self.reset_after_secs),
+            client_grace_secs: o.client_grace_secs.unwrap_or(self.client_grace_secs),
+            failback_interval_secs: o
+                .failback_interval_secs
+                .unwrap_or(self.failback_interval_secs),
+            jitter: o.jitter.unwrap_or(self.jitter),
+            auth_failure: o.auth_failure.unwrap_or(self.auth_failure),
+            auth_failure_delay_secs: o
+                .auth_failure_delay_secs
+                .unwrap_or(self.auth_failure_delay_secs),
+        }
+    }
+
+    /// Effective delay strategy: `backoff` if set, else from `use_exponential_backoff`
+    pub fn strategy(&self) -> BackoffStrategy {
+        self.backoff.unwrap_or(if self.use_exponential_backoff {
+            BackoffStrategy::Exponential
+        } else {
+            BackoffStrategy::Fixed
+        })
+    }
 }
 
 fn default_max_retries() -> u32 {
@@ -230,6 +1212,18 @@ fn default_use_exponential() -> bool {
     true
 }
 
+fn default_reset_after() -> u64 {
+    60
+}
+
+fn default_client_grace() -> u64 {
+    10
+}
+
+fn default_auth_failure_delay() -> u64 {
+    300
+}
+
 impl Default for ReconnectionConfig {
     fn default() -> Self {
         Self {
@@ -237,31 +1231,276 @@ impl Default for ReconnectionConfig {
             initial_delay_secs: default_initial_delay(),
             max_delay_secs: default_max_delay(),
             use_exponential_backoff: default_use_exponential(),
+            backoff: None,
+            reset_after_secs: default_reset_after(),
+            client_grace_secs: default_client_grace(),
+            failback_interval_secs: 0,
+            jitter: false,
+            auth_failure: AuthFailurePolicy::default(),
+            auth_failure_delay_secs: default_auth_failure_delay(),
         }
     }
 }
 
 impl AppConfig {
-    /// Load configuration from a TOML file
+    /// JSON Schema of the configuration file, generated from these types
+    pub fn json_schema() -> RootSchema {
+        schemars::schema_for!(AppConfig)
+    }
+
+    /// Load configuration from a TOML file, merging hosts and channels from `include` files
     pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
-        let content = std::fs::read_to_string(path.as_ref())
-            .map_err(|e| AppError::Config(format!("Failed to read config file: {}", e)))?;
+        let path = path.as_ref();
+        let mut config = Self::parse_file(path, None)?;
+        config.record_origins(path);
+
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut visited = vec![std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())];
+        let include = config.include.clone();
+        config.merge_includes(&include, base_dir, &mut visited)?;
+
+        config.resolve_ssh_config_hosts(base_dir)?;
+
+        Ok(config)
+    }
 
-        let config: AppConfig = toml::from_str(&content)
-            .map_err(|e| AppError::Config(format!("Failed to parse config: {}", e)))?;
+    /// Read and parse a single config file without following includes.
+    /// Older layouts are upgraded in memory (see `config_migrate`).
+    /// `${VAR}` references in hosts/channels are expanded with `vars` (or the file's own `[vars]`).
+    fn parse_file(path: &Path, vars: Option<&BTreeMap<String, String>>) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            AppError::Config(format!(
+                "Failed to read config file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
 
+        let parse_error = |e: &dyn std::fmt::Display| {
+            AppError::Config(format!("Failed to parse config {}: {}", path.display(), e))
+        };
+
+        let (config, report) = Self::parse_text(&content, ConfigFormat::from_path(path), vars)
+            .map_err(|e| parse_error(&e))?;
+        if report.is_upgrade() {
+            warn!(
+                "{} uses config version {} (current: {}); run `ssh-channels-hub migrate` to upgrade it",
+                path.display(),
+                report.from_version,
+                report.to_version
+            );
+        }
         Ok(config)
     }
 
+    /// Parse the text of one config file: convert it to TOML, upgrade older layouts, expand
+    /// `${VAR}` references and deserialize. Includes are not followed.
+    pub(crate) fn parse_text(
+        content: &str,
+        format: ConfigFormat,
+        vars: Option<&BTreeMap<String, String>>,
+    ) -> std::result::Result<(Self, crate::config_migrate::MigrationReport), String> {
+        let content =
+            crate::config_format::to_toml_text(content, format).map_err(|e| e.to_string())?;
+        let mut doc: toml_edit::DocumentMut = content.parse().map_err(|e| format!("{}", e))?;
+        let report = crate::config_migrate::migrate(&mut doc).map_err(|e| e.to_string())?;
+        let config = Self::from_document(doc, vars).map_err(|e| e.to_string())?;
+        Ok((config, report))
+    }
+
+    /// Deserialize a (current-version) TOML document, expanding `${VAR}` references
+    pub fn from_document(
+        mut doc: toml_edit::DocumentMut,
+        vars: Option<&BTreeMap<String, String>>,
+    ) -> Result<Self> {
+        let vars = match vars {
+            Some(vars) => vars.clone(),
+            None => crate::interpolate::read_vars(&doc)?,
+        };
+        crate::interpolate::interpolate_document(&mut doc, &vars)?;
+
+        Ok(toml::from_str(&doc.to_string())?)
+    }
+
+    /// Remember `path` as the origin of every host/channel not yet recorded
+    fn record_origins(&mut self, path: &Path) {
+        for host in &self.hosts {
+            self.origins
+                .hosts
+                .entry(host.name.clone())
+                .or_insert_with(|| path.to_path_buf());
+        }
+        for conn in &self.channels {
+            self.origins
+                .channels
+                .entry(conn.name.clone())
+                .or_insert_with(|| path.to_path_buf());
+        }
+    }
+
+    /// Merge hosts/channels of included files (recursively). Only `hosts`, `channels` and
+    /// `include` are taken from included files; names must be unique across files.
+    fn merge_includes(
+        &mut self,
+        patterns: &[String],
+        base_dir: &Path,
+        visited: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for pattern in patterns {
+            let expanded = crate::ssh_config::expand_tilde(Path::new(pattern))?;
+            let full = if expanded.is_relative() {
+                base_dir.join(expanded)
+            } else {
+                expanded
+            };
+
+            let mut files: Vec<PathBuf> = glob::glob(&full.to_string_lossy())
+                .map_err(|e| {
+                    AppError::Config(format!("Invalid include pattern '{}': {}", pattern, e))
+                })?
+                .filter_map(|p| p.ok())
+                .collect();
+            files.sort();
+
+            if files.is_empty() {
+                debug!(pattern = %pattern, "Config include matched no files");
+            }
+
+            for file in files {
+                let canonical = std::fs::canonicalize(&file).unwrap_or_else(|_| file.clone());
+                if visited.contains(&canonical) {
+                    continue;
+                }
+                visited.push(canonical);
+
+                let vars = self.vars.clone();
+                let included = Self::parse_file(&file, Some(&vars))?;
+
+                // Names must be unique across files and within the included file itself
+                let duplicate = |kind: &str, name: &str, existing: Option<&PathBuf>| {
+                    let defined = match existing {
+                        Some(existing) => {
+                            format!("in {} and {}", existing.display(), file.display())
+                        }
+                        None => format!("twice in {}", file.display()),
+                    };
+                    AppError::Config(format!("Duplicate {} '{}' defined {}", kind, name, defined))
+                };
+                let mut seen = HashSet::new();
+                for host in &included.hosts {
+                    if let Some(existing) = self.origins.hosts.get(&host.name) {
+                        return Err(duplicate("host", &host.name, Some(existing)));
+                    }
+                    if !seen.insert(host.name.as_str()) {
+                        return Err(duplicate("host", &host.name, None));
+                    }
+                }
+                let mut seen = HashSet::new();
+                for conn in &included.channels {
+                    if let Some(existing) = self.origins.channels.get(&conn.name) {
+                        return Err(duplicate("channel", &conn.name, Some(existing)));
+                    }
+                    if !seen.insert(conn.name.as_str()) {
+                        return Err(duplicate("channel", &conn.name, None));
+                    }
+                }
+
+                let Self {
+                    hosts,
+                    channels,
+                    include,
+                    ..
+                } = included;
+                let start_hosts = self.hosts.len();
+                let start_channels = self.channels.len();
+                self.hosts.extend(hosts);
+                self.channels.extend(channels);
+                for host in &self.hosts[start_hosts..] {
+                    self.origins.hosts.insert(host.name.clone(), file.clone());
+                }
+                for conn in &self.channels[start_channels..] {
+                    self.origins
+                        .channels
+                        .insert(conn.name.clone(), file.clone());
+                }
+
+                let file_dir = file.parent().unwrap_or_else(|| Path::new("."));
+                self.merge_includes(&include, file_dir, visited)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add hosts for channels that reference ~/.ssh/config aliases.
+    ///
+    /// A `hostname` of the form `ssh:alias` is always resolved through the SSH config
+    /// (`ssh_config` if set, else ~/.ssh/config). When `ssh_config` is set, plain hostnames that
+    /// match no `[[hosts]]` entry are resolved through it too. Resolved hosts are added to
+    /// `hosts` under the channel's `hostname`, so the rest of the app treats them like any host.
+    pub fn resolve_ssh_config_hosts(&mut self, base_dir: &Path) -> Result<()> {
+        let mut references: Vec<String> = Vec::new();
+        for hostname in self.channels.iter().flat_map(|c| c.hostname.iter()) {
+            let defined = self.hosts.iter().any(|h| h.name == hostname);
+            if defined || references.iter().any(|r| r == hostname) {
+                continue;
+            }
+            if hostname.starts_with(SSH_CONFIG_HOST_PREFIX) || self.ssh_config.is_some() {
+                references.push(hostname.to_string());
+            }
+        }
+
+        if references.is_empty() {
+            return Ok(());
+        }
+
+        let ssh_config_path = match &self.ssh_config {
+            Some(p) if p.is_relative() && !p.starts_with("~") => base_dir.join(p),
+            Some(p) => p.clone(),
+            None => crate::ssh_config::default_ssh_config_path(),
+        };
+        let ssh_config = crate::ssh_config::SshConfig::from_file(&ssh_config_path)?;
+        let declared = ssh_config.host_aliases();
+
+        for reference in references {
+            let alias = reference
+                .strip_prefix(SSH_CONFIG_HOST_PREFIX)
+                .unwrap_or(&reference);
+            let entry = ssh_config.resolve(alias);
+
+            // Plain names only resolve when the SSH config actually knows the alias
+            let explicit = reference.starts_with(SSH_CONFIG_HOST_PREFIX);
+            if !explicit && entry.hostname.is_none() && !declared.iter().any(|a| a == alias) {
+                continue;
+            }
+
+            self.origins
+                .hosts
+                .insert(reference.clone(), ssh_config_path.clone());
+            self.hosts
+                .push(host_from_ssh_config_entry(reference.clone(), entry)?);
+        }
+
+        Ok(())
+    }
+
     /// Default config file candidates (first existing wins; if none exist, first is used).
-    /// Order: current directory `configs.toml`, then platform config dir `config.toml`.
+    /// Order: current directory `configs.{toml,yaml,yml,json}`, then platform config dir
+    /// `config.{toml,yaml,yml,json}`.
     pub fn default_path_candidates() -> Vec<PathBuf> {
+        const EXTENSIONS: [&str; 4] = ["toml", "yaml", "yml", "json"];
         let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-        let mut candidates = vec![current_dir.join("configs.toml")];
+        let mut candidates: Vec<PathBuf> = EXTENSIONS
+            .iter()
+            .map(|ext| current_dir.join(format!("configs.{}", ext)))
+            .collect();
         if let Some(mut path) = dirs::config_dir() {
             path.push("ssh-channels-hub");
-            path.push("config.toml");
-            candidates.push(path);
+            candidates.extend(
+                EXTENSIONS
+                    .iter()
+                    .map(|ext| path.join(format!("config.{}", ext))),
+            );
         }
         candidates
     }
@@ -279,11 +1518,14 @@ impl AppConfig {
             .unwrap_or_else(|| PathBuf::from("configs.toml"))
     }
 
-    /// Generate configuration from SSH config entries
+    /// Generate configuration from SSH config entries.
+    /// LocalForward / RemoteForward / DynamicForward directives become channels of the generated hosts.
     pub fn from_ssh_config_entries(entries: Vec<crate::ssh_config::SshConfigEntry>) -> Self {
         let mut hosts = Vec::new();
+        let mut channels = Vec::new();
 
         for entry in entries.into_iter() {
+            warn_unsupported_jump(&entry);
             // Skip entries without required fields
             let hostname = match entry.hostname {
                 Some(h) => h,
@@ -308,111 +1550,215 @@ impl AppConfig {
                 }
             };
 
+            for fwd in &entry.local_forwards {
+                channels.push(ConnectionConfig {
+                    name: format!("{}-L{}", entry.host, fwd.bind_port),
+                    hostname: entry.host.clone().into(),
+                    channel_type: None,
+                    ports: PortForward {
+                        local_port: Some(fwd.bind_port),
+                        dest_port: fwd.connect_port,
+                    },
+                    dest_host: fwd.connect_host.clone(),
+                    listen_host: listen_host_from_bind_address(fwd.bind_address.as_deref()),
+                    reconnection: None,
+                    sessions: None,
+                    balance: None,
+                    on_up: None,
+                    on_down: None,
+                    on_fail: None,
+                    health_check: None,
+                });
+            }
+
+            for fwd in &entry.remote_forwards {
+                // forwarded-tcpip runtime params: local_port = local connect port, dest_port = remote bind port
+                channels.push(ConnectionConfig {
+                    name: format!("{}-R{}", entry.host, fwd.bind_port),
+                    hostname: entry.host.clone().into(),
+                    channel_type: Some("forwarded-tcpip".to_string()),
+                    ports: PortForward {
+                        local_port: Some(fwd.connect_port),
+                        dest_port: fwd.bind_port,
+                    },
+                    dest_host: fwd.connect_host.clone(),
+                    listen_host: default_listen_host(),
+                    reconnection: None,
+                    sessions: None,
+                    balance: None,
+                    on_up: None,
+                    on_down: None,
+                    on_fail: None,
+                    health_check: None,
+                });
+            }
+
+            for fwd in &entry.dynamic_forwards {
+                channels.push(ConnectionConfig {
+                    name: format!("{}-D{}", entry.host, fwd.bind_port),
+                    hostname: entry.host.clone().into(),
+                    channel_type: Some("dynamic".to_string()),
+                    ports: PortForward {
+                        local_port: Some(fwd.bind_port),
+                        dest_port: 0,
+                    },
+                    dest_host: default_destination_host(),
+                    listen_host: listen_host_from_bind_address(fwd.bind_address.as_deref()),
+                    reconnection: None,
+                    sessions: None,
+                    balance: None,
+                    on_up: None,
+                    on_down: None,
+                    on_fail: None,
+                    health_check: None,
+                });
+            }
+
             let host_cfg = HostConfig {
                 name: entry.host.clone(),
                 host: hostname,
                 port: entry.port.unwrap_or(22), // Use port from SSH config or default to 22
                 username,
                 auth,
+                keepalive_interval_secs: entry.server_alive_interval,
+                keepalive_max: entry.server_alive_count_max,
+                reconnection: None,
             };
 
             hosts.push(host_cfg);
         }
 
+        // The same port on different bind addresses would otherwise give two channels one name
+        let mut taken = HashSet::new();
+        for channel in &mut channels {
+            let base = channel.name.clone();
+            let mut n = 1;
+            while !taken.insert(channel.name.clone()) {
+                n += 1;
+                channel.name = format!("{}-{}", base, n);
+            }
+        }
+
         Self {
+            version: default_config_version(),
+            include: Vec::new(),
+            vars: BTreeMap::new(),
+            ssh_config: None,
             hosts,
-            channels: Vec::new(), // Generate command doesn't create channels
+            channels,
             reconnection: ReconnectionConfig::default(),
+            metrics: None,
+            audit: None,
+            hooks: None,
+            logging: None,
+            origins: ConfigOrigins::default(),
         }
     }
 
+    /// Hosts a channel's `hostname` refers to, preferred first
+    pub fn channel_hosts(
+        &self,
+        conn: &ConnectionConfig,
+    ) -> std::result::Result<Vec<&HostConfig>, ChannelError> {
+        conn.hostname
+            .iter()
+            .map(|name| {
+                self.hosts.iter().find(|h| h.name == name).ok_or_else(|| {
+                    ChannelError::new(
+                        "hostname",
+                        format!("Channel '{}' references unknown host '{}'", conn.name, name),
+                    )
+                })
+            })
+            .collect()
+    }
+
     /// Build runtime channel configs by combining hosts and channels
     pub fn build_channels(&self) -> Result<Vec<ChannelConfig>> {
         let mut channels = Vec::new();
+        let global_hooks = self.hooks.clone().unwrap_or_default();
+        // A channel's own hook wins; an empty command turns the default off
+        let pick_hook = |own: &Option<String>, default: &Option<String>| {
+            own.as_ref()
+                .or(default.as_ref())
+                .filter(|command| !command.trim().is_empty())
+                .cloned()
+        };
 
         for conn in &self.channels {
-            let host_cfg = self
-                .hosts
-                .iter()
-                .find(|h| h.name == conn.hostname)
-                .ok_or_else(|| {
-                    AppError::Config(format!(
-                        "Channel '{}' references unknown host '{}'",
-                        conn.name, conn.hostname
-                    ))
-                })?;
-
-            let channel_type = conn
-                .channel_type
-                .as_deref()
-                .unwrap_or("direct-tcpip")
-                .to_string();
-
-            let params = match channel_type.as_str() {
-                "forwarded-tcpip" => {
-                    let local_connect_port = conn.ports.local_port.ok_or_else(|| {
-                        AppError::Config(format!(
-                            "Channel '{}': forwarded-tcpip requires ports local:remote (e.g. 80:8022)",
-                            conn.name
-                        ))
-                    })?;
-                    ChannelTypeParams::ForwardedTcpIp {
-                        remote_bind_port: conn.ports.dest_port,
-                        local_connect_host: conn.dest_host.clone(),
-                        local_connect_port,
-                    }
-                }
-                "session" => ChannelTypeParams::Session { command: None },
-                "direct-tcpip" => {
-                    let local_port = conn.ports.local_port.ok_or_else(|| {
-                        AppError::Config(format!(
-                            "Channel '{}': direct-tcpip requires ports local:remote (e.g. 8080:80)",
-                            conn.name
-                        ))
-                    })?;
-                    ChannelTypeParams::DirectTcpIp {
-                        listen_host: conn.listen_host.clone(),
-                        local_port,
-                        dest_host: conn.dest_host.clone(),
-                        dest_port: conn.ports.dest_port,
-                    }
-                }
-                unknown => {
-                    return Err(AppError::Config(format!(
-                        "Channel '{}': unknown channel_type '{}', expected 'direct-tcpip', 'forwarded-tcpip', or 'session'",
-                        conn.name, unknown
-                    )));
-                }
-            };
+            let hosts = self.channel_hosts(conn)?;
+            // Host-level reconnection overrides come from the preferred host
+            let host_cfg = hosts[0];
+            let params = conn.params()?;
+            let sessions = conn.session_count(&params)?;
+
+            let reconnection = self
+                .reconnection
+                .with_override(host_cfg.reconnection.as_ref())
+                .with_override(conn.reconnection.as_ref());
+
+            let health_check = conn.health_check(&params)?;
 
             channels.push(ChannelConfig {
                 name: conn.name.clone(),
-                host: host_cfg.host.clone(),
-                port: host_cfg.port,
-                username: host_cfg.username.clone(),
-                auth: host_cfg.auth.clone(),
-                channel_type,
+                hosts: hosts.into_iter().map(HostEndpoint::from_host).collect(),
+                channel_type: conn.channel_type().to_string(),
                 params,
+                reconnection,
+                sessions,
+                balance: conn.balance.unwrap_or_default(),
+                hooks: ChannelHooks {
+                    on_up: pick_hook(&conn.on_up, &global_hooks.on_up),
+                    on_down: pick_hook(&conn.on_down, &global_hooks.on_down),
+                    on_fail: pick_hook(&conn.on_fail, &global_hooks.on_fail),
+                    timeout: Duration::from_secs(global_hooks.timeout_secs),
+                },
+                health_check,
             });
         }
 
         Ok(channels)
     }
 
-    /// Save configuration to a TOML file
-    pub fn to_file(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| AppError::Config(format!("Failed to serialize config: {}", e)))?;
+    /// Copy of the configuration with passwords and passphrases replaced, for display
+    pub fn redacted(&self) -> Self {
+        const REDACTED: &str = "********";
+        let mut config = self.clone();
+        for host in &mut config.hosts {
+            match &mut host.auth {
+                AuthConfig::Password { password } => *password = REDACTED.to_string(),
+                AuthConfig::Key { passphrase, .. } => {
+                    if passphrase.is_some() {
+                        *passphrase = Some(REDACTED.to_string());
+                    }
+                }
+            }
+        }
+        config
+    }
 
-        // Add comments before each [[hosts]] entry
-        let content_with_comments = self.add_host_comments(&content);
+    /// Save configuration to a file in the given format
+    pub fn to_file(&self, path: impl AsRef<std::path::Path>, format: ConfigFormat) -> Result<()> {
+        let content = self.to_string_as(format)?;
 
-        std::fs::write(path.as_ref(), content_with_comments)
+        std::fs::write(path.as_ref(), content)
             .map_err(|e| AppError::Config(format!("Failed to write config file: {}", e)))?;
 
         Ok(())
     }
 
+    /// Serialize in the given format; TOML output gets a comment before each [[hosts]] entry
+    pub fn to_string_as(&self, format: ConfigFormat) -> Result<String> {
+        match format {
+            ConfigFormat::Toml => {
+                let content = toml::to_string_pretty(self)
+                    .map_err(|e| AppError::Config(format!("Failed to serialize config: {}", e)))?;
+                Ok(self.add_host_comments(&content))
+            }
+            other => other.serialize(self),
+        }
+    }
+
     /// Add comments before each [[hosts]] entry
     fn add_host_comments(&self, content: &str) -> String {
         let mut result = String::new();
@@ -0,0 +1,171 @@
+use crate::error::{AppError, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// On-disk configuration format, chosen by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Format for a path: `.yaml`/`.yml` and `.json` are recognised, anything else is TOML
+    pub fn from_path(path: &Path) -> Self {
+        Self::from_extension(path).unwrap_or(ConfigFormat::Toml)
+    }
+
+    /// Format for a path only if its extension is a known one
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Preferred file extension
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    /// Parse config text of this format into a format-neutral value
+    fn parse(self, content: &str) -> Result<serde_json::Value> {
+        match self {
+            ConfigFormat::Toml => Ok(toml::from_str(content)?),
+            ConfigFormat::Yaml => serde_yaml::from_str(content)
+                .map_err(|e| AppError::Config(format!("Invalid YAML: {}", e))),
+            ConfigFormat::Json => serde_json::from_str(content)
+                .map_err(|e| AppError::Config(format!("Invalid JSON: {}", e))),
+        }
+    }
+
+    /// Serialize any config value (an `AppConfig` or a raw value) in this format
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String> {
+        let error = |e: &dyn std::fmt::Display| {
+            AppError::Config(format!("Failed to serialize config as {}: {}", self, e))
+        };
+        match self {
+            ConfigFormat::Toml => {
+                // TOML has no null; drop absent optional values instead of failing
+                let mut value = serde_json::to_value(value).map_err(|e| error(&e))?;
+                strip_nulls(&mut value);
+                toml::to_string_pretty(&value).map_err(|e| error(&e))
+            }
+            ConfigFormat::Yaml => serde_yaml::to_string(value).map_err(|e| error(&e)),
+            ConfigFormat::Json => serde_json::to_string_pretty(value)
+                .map(|s| s + "\n")
+                .map_err(|e| error(&e)),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Re-encode YAML/JSON config text as TOML so loading (vars, `${VAR}` expansion) has one path
+pub fn to_toml_text(content: &str, format: ConfigFormat) -> Result<String> {
+    if format == ConfigFormat::Toml {
+        return Ok(content.to_string());
+    }
+    ConfigFormat::Toml.serialize(&format.parse(content)?)
+}
+
+/// Translate a config file between formats as written: `include`, `[vars]` and `${VAR}`
+/// references are kept, not resolved. Comments are not carried over.
+pub fn convert(content: &str, from: ConfigFormat, to: ConfigFormat) -> Result<String> {
+    let value = from.parse(content)?;
+    if !value.is_object() {
+        return Err(AppError::Config(
+            "Config must be a table/mapping at the top level".to_string(),
+        ));
+    }
+    to.serialize(&value)
+}
+
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const TOML: &str = r#"
+[vars]
+user = "deploy"
+
+[[hosts]]
+name = "h"
+host = "example.com"
+username = "${user}"
+
+[hosts.auth]
+type = "key"
+key_path = "~/.ssh/id_ed25519"
+
+[[channels]]
+name = "web"
+hostname = "h"
+ports = "${WEB_PORT:-8080}:80"
+"#;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            ConfigFormat::from_path(&PathBuf::from("a.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(&PathBuf::from("a.JSON")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(&PathBuf::from("a.conf")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(ConfigFormat::from_extension(&PathBuf::from("a.conf")), None);
+    }
+
+    #[test]
+    fn test_convert_round_trip_keeps_placeholders() {
+        let yaml = convert(TOML, ConfigFormat::Toml, ConfigFormat::Yaml).unwrap();
+        assert!(yaml.contains("username: ${user}"));
+
+        let json = convert(&yaml, ConfigFormat::Yaml, ConfigFormat::Json).unwrap();
+        assert!(json.contains("\"ports\": \"${WEB_PORT:-8080}:80\""));
+
+        let toml = convert(&json, ConfigFormat::Json, ConfigFormat::Toml).unwrap();
+        let original: toml::Value = toml::from_str(TOML).unwrap();
+        let round_tripped: toml::Value = toml::from_str(&toml).unwrap();
+        assert_eq!(original, round_tripped);
+    }
+
+    #[test]
+    fn test_convert_rejects_non_mapping() {
+        assert!(convert("- a\n- b\n", ConfigFormat::Yaml, ConfigFormat::Toml).is_err());
+    }
+}
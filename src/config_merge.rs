@@ -0,0 +1,397 @@
+use crate::config::{AppConfig, AuthConfig, ConnectionConfig, HostConfig};
+use crate::config_format::ConfigFormat;
+use crate::error::{AppError, Result};
+use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table, TableLike, Value};
+
+/// A single change applied while merging generated hosts/channels into an existing config
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeChange {
+    HostAdded {
+        name: String,
+        host: String,
+    },
+    HostUpdated {
+        name: String,
+        field: String,
+        old: String,
+        new: String,
+    },
+    ChannelAdded {
+        name: String,
+        hostname: String,
+    },
+}
+
+impl std::fmt::Display for MergeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeChange::HostAdded { name, host } => write!(f, "+ host '{}' ({})", name, host),
+            MergeChange::HostUpdated {
+                name,
+                field,
+                old,
+                new,
+            } => write!(f, "~ host '{}': {} {} -> {}", name, field, old, new),
+            MergeChange::ChannelAdded { name, hostname } => {
+                write!(f, "+ channel '{}' (host: {})", name, hostname)
+            }
+        }
+    }
+}
+
+/// Result of merging: the new file content and the list of changes
+#[derive(Debug, Clone)]
+pub struct MergeOutcome {
+    pub content: String,
+    pub changes: Vec<MergeChange>,
+}
+
+/// Merge generated hosts and channels into existing TOML text, preserving comments and formatting.
+///
+/// New hosts are appended, changed host fields are updated in place, existing channels and
+/// `[reconnection]` are left untouched and only channels with new names are appended.
+/// A generated password placeholder never replaces an existing auth section.
+pub fn merge_generated(existing: &str, generated: &AppConfig) -> Result<MergeOutcome> {
+    // Refuse to edit a file that does not load as a valid config, read the way the service reads it
+    AppConfig::parse_text(existing, ConfigFormat::Toml, None)
+        .map_err(|e| AppError::Config(format!("Failed to parse existing config: {}", e)))?;
+
+    let mut doc: DocumentMut = existing
+        .parse()
+        .map_err(|e| AppError::Config(format!("Failed to parse existing config: {}", e)))?;
+    let mut changes = Vec::new();
+    // New tables go after everything else so they never split existing comments from their table
+    let mut next_position = max_position(doc.as_table()) + 1;
+
+    let hosts = array_of_tables_mut(&mut doc, "hosts")?;
+    for host in &generated.hosts {
+        let existing_table = hosts
+            .iter_mut()
+            .find(|t| t.get("name").and_then(|v| v.as_str()) == Some(host.name.as_str()));
+        match existing_table {
+            Some(table) => update_host_table(table, host, &mut changes),
+            None => {
+                hosts.push(new_host_table(host, &mut next_position));
+                changes.push(MergeChange::HostAdded {
+                    name: host.name.clone(),
+                    host: host.host.clone(),
+                });
+            }
+        }
+    }
+
+    if !generated.channels.is_empty() {
+        let channels = array_of_tables_mut(&mut doc, "channels")?;
+        for conn in &generated.channels {
+            let exists = channels
+                .iter()
+                .any(|t| t.get("name").and_then(|v| v.as_str()) == Some(conn.name.as_str()));
+            if !exists {
+                channels.push(new_channel_table(conn, &mut next_position)?);
+                changes.push(MergeChange::ChannelAdded {
+                    name: conn.name.clone(),
+                    hostname: conn.hostname.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(MergeOutcome {
+        content: doc.to_string(),
+        changes,
+    })
+}
+
+/// Highest document position used by any table (recursively)
+pub(crate) fn max_position(table: &Table) -> usize {
+    let mut max = table.position().unwrap_or(0);
+    for (_, item) in table.iter() {
+        match item {
+            Item::Table(t) => max = max.max(max_position(t)),
+            Item::ArrayOfTables(tables) => {
+                for t in tables.iter() {
+                    max = max.max(max_position(t));
+                }
+            }
+            _ => {}
+        }
+    }
+    max
+}
+
+/// Get (or create) a top-level array of tables
+fn array_of_tables_mut<'a>(doc: &'a mut DocumentMut, key: &str) -> Result<&'a mut ArrayOfTables> {
+    if !doc.contains_key(key) {
+        doc.insert(key, Item::ArrayOfTables(ArrayOfTables::new()));
+    }
+    doc[key]
+        .as_array_of_tables_mut()
+        .ok_or_else(|| AppError::Config(format!("'{}' must be an array of tables", key)))
+}
+
+/// Update changed fields of an existing `[[hosts]]` table
+fn update_host_table(table: &mut Table, host: &HostConfig, changes: &mut Vec<MergeChange>) {
+    let mut record = |field: &str, old: String, new: String| {
+        changes.push(MergeChange::HostUpdated {
+            name: host.name.clone(),
+            field: field.to_string(),
+            old,
+            new,
+        });
+    };
+
+    let old_host = table.get("host").and_then(|v| v.as_str()).unwrap_or("");
+    if old_host != host.host {
+        record("host", old_host.to_string(), host.host.clone());
+        set_preserving_decor(table, "host", Value::from(host.host.as_str()));
+    }
+
+    let old_port = table.get("port").and_then(|v| v.as_integer()).unwrap_or(22);
+    if old_port != i64::from(host.port) {
+        record("port", old_port.to_string(), host.port.to_string());
+        set_preserving_decor(table, "port", Value::from(i64::from(host.port)));
+    }
+
+    let old_user = table.get("username").and_then(|v| v.as_str()).unwrap_or("");
+    if old_user != host.username {
+        record("username", old_user.to_string(), host.username.clone());
+        set_preserving_decor(table, "username", Value::from(host.username.as_str()));
+    }
+
+    let keepalive = [
+        ("keepalive_interval_secs", host.keepalive_interval_secs),
+        ("keepalive_max", host.keepalive_max.map(|n| n as u64)),
+    ];
+    for (field, new) in keepalive {
+        // Not set in the ssh config: keep whatever the file has
+        let Some(new) = new else {
+            continue;
+        };
+        let old = table.get(field).and_then(|v| v.as_integer());
+        if old != Some(new as i64) {
+            let old = old.map_or_else(|| "unset".to_string(), |v| v.to_string());
+            record(field, old, new.to_string());
+            set_preserving_decor(table, field, Value::from(new as i64));
+        }
+    }
+
+    // Only key paths come from ssh config; a password placeholder never overrides anything
+    let AuthConfig::Key { key_path, .. } = &host.auth else {
+        return;
+    };
+    let new_path = key_path.to_string_lossy().to_string();
+
+    match table.get_mut("auth").and_then(Item::as_table_like_mut) {
+        Some(auth) if auth.get("type").and_then(|v| v.as_str()) == Some("key") => {
+            let old_path = auth
+                .get("key_path")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            if old_path != new_path {
+                set_preserving_decor(auth, "key_path", Value::from(new_path.as_str()));
+                record("auth.key_path", old_path, new_path);
+            }
+        }
+        _ => {
+            table.insert("auth", Item::Table(auth_table(&host.auth)));
+            record(
+                "auth",
+                "password".to_string(),
+                format!("key ({})", new_path),
+            );
+        }
+    }
+}
+
+/// Replace a value while keeping its surrounding whitespace and trailing comment
+fn set_preserving_decor(table: &mut dyn TableLike, key: &str, new: Value) {
+    match table.get_mut(key).and_then(Item::as_value_mut) {
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = new;
+            *old.decor_mut() = decor;
+        }
+        None => {
+            table.insert(key, value(new));
+        }
+    }
+}
+
+fn new_host_table(host: &HostConfig, next_position: &mut usize) -> Table {
+    let mut table = Table::new();
+    table.set_position(*next_position);
+    table
+        .decor_mut()
+        .set_prefix(format!("\n# Host: {} ({})\n", host.name, host.host));
+    table.insert("name", value(host.name.as_str()));
+    table.insert("host", value(host.host.as_str()));
+    table.insert("port", value(i64::from(host.port)));
+    table.insert("username", value(host.username.as_str()));
+    if let Some(interval) = host.keepalive_interval_secs {
+        table.insert("keepalive_interval_secs", value(interval as i64));
+    }
+    if let Some(max) = host.keepalive_max {
+        table.insert("keepalive_max", value(max as i64));
+    }
+    let mut auth = auth_table(&host.auth);
+    auth.set_position(*next_position + 1);
+    table.insert("auth", Item::Table(auth));
+    *next_position += 2;
+    table
+}
+
+fn auth_table(auth: &AuthConfig) -> Table {
+    let mut table = Table::new();
+    match auth {
+        AuthConfig::Password { password } => {
+            table.insert("type", value("password"));
+            table.insert("password", value(password.as_str()));
+        }
+        AuthConfig::Key {
+            key_path,
+            passphrase,
+        } => {
+            table.insert("type", value("key"));
+            table.insert("key_path", value(key_path.to_string_lossy().as_ref()));
+            if let Some(passphrase) = passphrase {
+                table.insert("passphrase", value(passphrase.as_str()));
+            }
+        }
+    }
+    table
+}
+
+fn new_channel_table(conn: &ConnectionConfig, next_position: &mut usize) -> Result<Table> {
+    // Reuse serde so field defaults and the ports format stay in one place
+    let text = toml::to_string(conn)
+        .map_err(|e| AppError::Config(format!("Failed to serialize channel: {}", e)))?;
+    let doc: DocumentMut = text
+        .parse()
+        .map_err(|e| AppError::Config(format!("Failed to serialize channel: {}", e)))?;
+    let mut table = doc.as_table().clone();
+    table.decor_mut().set_prefix("\n");
+    table.set_position(*next_position);
+    *next_position += 1;
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_config::SshConfig;
+
+    const EXISTING: &str = r#"# My tunnels
+[[channels]]
+name = "db"          # production database
+hostname = "prod"
+ports = "5432:5432"
+
+[reconnection]
+max_retries = 5
+
+[[hosts]]
+name = "prod"
+host = "old.example.com"   # moved last year
+port = 22
+username = "deploy"
+
+[hosts.auth]
+type = "password"
+password = "s3cret"
+"#;
+
+    /// What `generate` produces for two aliases: `prod` without a key, `staging` with a forward;
+    /// each sets one keepalive option
+    fn generated() -> AppConfig {
+        let ssh_config = SshConfig::parse(
+            r#"
+Host prod
+    HostName new.example.com
+    Port 2222
+    User deploy
+    ServerAliveInterval 30
+
+Host staging
+    HostName staging.example.com
+    User ci
+    IdentityFile /keys/ci
+    ServerAliveCountMax 5
+    LocalForward 8080 127.0.0.1:80
+"#,
+        )
+        .unwrap();
+        AppConfig::from_ssh_config_entries(ssh_config.entries())
+    }
+
+    #[test]
+    fn test_merge_preserves_comments_and_channels() {
+        let outcome = merge_generated(EXISTING, &generated()).unwrap();
+
+        assert!(outcome.content.starts_with("# My tunnels\n"));
+        assert!(outcome.content.contains("# production database"));
+        assert!(outcome
+            .content
+            .contains("host = \"new.example.com\"   # moved last year"));
+        assert!(outcome.content.contains("password = \"s3cret\""));
+        assert!(!outcome.content.contains("CHANGE_ME"));
+
+        let merged: AppConfig = toml::from_str(&outcome.content).unwrap();
+        assert_eq!(merged.reconnection.max_retries, 5);
+        assert_eq!(merged.hosts.len(), 2);
+        assert_eq!(merged.hosts[0].port, 2222);
+        assert_eq!(merged.hosts[0].keepalive_interval_secs, Some(30));
+        assert_eq!(merged.hosts[0].keepalive_max, None);
+        assert_eq!(merged.hosts[1].keepalive_interval_secs, None);
+        assert_eq!(merged.hosts[1].keepalive_max, Some(5));
+        assert_eq!(merged.channels.len(), 2);
+        assert_eq!(merged.channels[0].name, "db");
+    }
+
+    #[test]
+    fn test_merge_reports_changes() {
+        let outcome = merge_generated(EXISTING, &generated()).unwrap();
+        let lines: Vec<String> = outcome.changes.iter().map(|c| c.to_string()).collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "~ host 'prod': host old.example.com -> new.example.com",
+                "~ host 'prod': port 22 -> 2222",
+                "~ host 'prod': keepalive_interval_secs unset -> 30",
+                "+ host 'staging' (staging.example.com)",
+                "+ channel 'staging-L8080' (host: staging)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_accepts_files_the_service_loads() {
+        // ${VAR} in a non-string field only parses after interpolation
+        let interpolated = EXISTING.replace(
+            "ports = \"5432:5432\"",
+            "ports = \"${db_port}:5432\"\n\n[vars]\ndb_port = \"15432\"",
+        );
+        let outcome = merge_generated(&interpolated, &generated()).unwrap();
+        assert!(outcome.content.contains("\"${db_port}:5432\""));
+
+        // Version 0: channels carry their own host and auth, upgraded on load
+        let legacy = r#"[[channels]]
+name = "db"
+host = "db.example.com"
+username = "deploy"
+ports = "5432:5432"
+auth = { type = "password", password = "s3cret" }
+"#;
+        let outcome = merge_generated(legacy, &generated()).unwrap();
+        assert!(outcome.content.starts_with(legacy));
+    }
+
+    #[test]
+    fn test_merge_is_idempotent() {
+        let first = merge_generated(EXISTING, &generated()).unwrap();
+        let second = merge_generated(&first.content, &generated()).unwrap();
+        assert!(second.changes.is_empty());
+        assert_eq!(first.content, second.content);
+    }
+}
@@ -0,0 +1,364 @@
+use crate::config_merge::max_position;
+use crate::error::{AppError, Result};
+use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table};
+
+/// Current config layout version, stored as the top-level `version` key
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Keys that made a `[[channels]]` entry carry its own host in the version 0 layout
+const LEGACY_HOST_KEYS: [&str; 4] = ["host", "port", "username", "auth"];
+
+/// One upgrade step from version `from` to `from + 1`
+struct Migration {
+    from: u32,
+    apply: fn(&mut DocumentMut) -> Result<Vec<String>>,
+}
+
+/// Upgrade steps in order; append new ones when the layout changes again
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    apply: split_hosts_from_channels,
+}];
+
+/// What `migrate` did to a document
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Human-readable list of edits (empty when the document was already current)
+    pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+    /// Whether the layout was upgraded (as opposed to only adding the `version` key)
+    pub fn is_upgrade(&self) -> bool {
+        self.from_version < self.to_version
+    }
+}
+
+/// Upgrade a config document to [`CONFIG_VERSION`] in place, keeping comments where possible.
+///
+/// Files without `version` are version 0 if they use the old channel-with-embedded-host
+/// layout, otherwise they are taken to be current and only get the `version` key.
+pub fn migrate(doc: &mut DocumentMut) -> Result<MigrationReport> {
+    let declared = declared_version(doc)?;
+    let from_version = declared.unwrap_or_else(|| {
+        if has_legacy_channels(doc) {
+            0
+        } else {
+            CONFIG_VERSION
+        }
+    });
+
+    let mut changes = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from_version) {
+        changes.extend((migration.apply)(doc)?);
+    }
+
+    if declared != Some(CONFIG_VERSION) {
+        set_version(doc);
+        changes.push(format!("set version = {}", CONFIG_VERSION));
+    }
+
+    Ok(MigrationReport {
+        from_version,
+        to_version: CONFIG_VERSION,
+        changes,
+    })
+}
+
+fn declared_version(doc: &DocumentMut) -> Result<Option<u32>> {
+    let Some(item) = doc.get("version") else {
+        return Ok(None);
+    };
+    let version = item
+        .as_integer()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| AppError::Config("'version' must be a non-negative integer".to_string()))?;
+    if version > CONFIG_VERSION {
+        return Err(AppError::Config(format!(
+            "Config version {} is newer than this build supports ({}); upgrade ssh-channels-hub",
+            version, CONFIG_VERSION
+        )));
+    }
+    Ok(Some(version))
+}
+
+/// Put `version` first among the top-level keys
+fn set_version(doc: &mut DocumentMut) {
+    let root = doc.as_table_mut();
+    root.remove("version");
+    let values: Vec<String> = root
+        .iter()
+        .filter(|(_, item)| item.is_value())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    let moved: Vec<_> = values
+        .iter()
+        .filter_map(|key| root.remove_entry(key))
+        .collect();
+
+    root.insert("version", value(i64::from(CONFIG_VERSION)));
+    for (key, item) in moved {
+        root.insert_formatted(&key, item);
+    }
+}
+
+fn has_legacy_channels(doc: &DocumentMut) -> bool {
+    doc.get("channels")
+        .and_then(Item::as_array_of_tables)
+        .is_some_and(|channels| channels.iter().any(is_legacy_channel))
+}
+
+fn is_legacy_channel(table: &Table) -> bool {
+    table.contains_key("host") || table.contains_key("auth")
+}
+
+/// Version 0 -> 1: every `[[channels]]` entry used to carry `host`, `port`, `username` and
+/// `auth` itself. Move those into `[[hosts]]` (one per distinct server/login) and make the
+/// channel reference it through `hostname`. Entries without `ports` become hosts only.
+fn split_hosts_from_channels(doc: &mut DocumentMut) -> Result<Vec<String>> {
+    if !has_legacy_channels(doc) {
+        return Ok(Vec::new());
+    }
+
+    let mut next_position = max_position(doc.as_table()) + 1;
+    let mut taken_names: Vec<String> = doc
+        .get("hosts")
+        .and_then(Item::as_array_of_tables)
+        .map(|hosts| {
+            hosts
+                .iter()
+                .filter_map(|t| t.get("name").and_then(Item::as_str).map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let channels = doc["channels"]
+        .as_array_of_tables()
+        .cloned()
+        .unwrap_or_default();
+    let mut kept = ArrayOfTables::new();
+    let mut new_hosts: Vec<(String, Table)> = Vec::new();
+    let mut changes = Vec::new();
+
+    for table in channels.iter() {
+        if !is_legacy_channel(table) {
+            kept.push(table.clone());
+            continue;
+        }
+
+        let name = table
+            .get("name")
+            .and_then(Item::as_str)
+            .ok_or_else(|| AppError::Config("Legacy channel without 'name'".to_string()))?
+            .to_string();
+        let identity = host_identity(table);
+
+        let host_name = match new_hosts.iter().find(|(id, _)| *id == identity) {
+            Some((_, host)) => host["name"].as_str().unwrap_or_default().to_string(),
+            None => {
+                let mut host_name = name.clone();
+                while taken_names.contains(&host_name) {
+                    host_name.push_str("-host");
+                }
+                taken_names.push(host_name.clone());
+                new_hosts.push((identity, host_table(table, &host_name, &mut next_position)));
+                changes.push(format!(
+                    "moved host settings of channel '{}' into [[hosts]] '{}'",
+                    name, host_name
+                ));
+                host_name
+            }
+        };
+
+        if table.contains_key("ports") {
+            kept.push(channel_table(table, &host_name));
+            changes.push(format!(
+                "channel '{}' now references host '{}'",
+                name, host_name
+            ));
+        } else {
+            changes.push(format!(
+                "channel '{}' had no ports and is now only host '{}'",
+                name, host_name
+            ));
+        }
+    }
+
+    if kept.is_empty() {
+        doc.remove("channels");
+    } else {
+        doc["channels"] = Item::ArrayOfTables(kept);
+    }
+    if !doc.contains_key("hosts") {
+        doc["hosts"] = Item::ArrayOfTables(ArrayOfTables::new());
+    }
+    let hosts = doc["hosts"]
+        .as_array_of_tables_mut()
+        .ok_or_else(|| AppError::Config("'hosts' must be an array of tables".to_string()))?;
+    for (_, host) in new_hosts {
+        hosts.push(host);
+    }
+
+    Ok(changes)
+}
+
+/// Server + login + credentials; legacy channels sharing these share one host
+fn host_identity(table: &Table) -> String {
+    let text = |key: &str| {
+        table
+            .get(key)
+            .and_then(Item::as_value)
+            .map(|v| v.to_string().trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut auth: Vec<String> = table
+        .get("auth")
+        .and_then(Item::as_table_like)
+        .map(|auth| {
+            auth.iter()
+                .map(|(k, v)| format!("{}={}", k, v.to_string().trim()))
+                .collect()
+        })
+        .unwrap_or_default();
+    auth.sort();
+    let port = table.get("port").and_then(Item::as_integer).unwrap_or(22);
+
+    format!(
+        "{}|{}|{}|{}",
+        text("host"),
+        port,
+        text("username"),
+        auth.join(",")
+    )
+}
+
+fn host_table(channel: &Table, name: &str, next_position: &mut usize) -> Table {
+    let mut host = Table::new();
+    host.set_position(*next_position);
+    host.decor_mut().set_prefix("\n");
+    host.insert("name", value(name));
+    for key in LEGACY_HOST_KEYS {
+        let Some(item) = channel.get(key) else {
+            continue;
+        };
+        let mut item = item.clone();
+        if let Item::Table(auth) = &mut item {
+            auth.set_position(*next_position + 1);
+        }
+        host.insert(key, item);
+    }
+    *next_position += 2;
+    host
+}
+
+/// The legacy channel without its host keys, with `hostname` right after `name`
+fn channel_table(legacy: &Table, host_name: &str) -> Table {
+    let mut channel = Table::new();
+    if let Some(position) = legacy.position() {
+        channel.set_position(position);
+    }
+    *channel.decor_mut() = legacy.decor().clone();
+    for (key, item) in legacy.iter() {
+        if LEGACY_HOST_KEYS.contains(&key) {
+            continue;
+        }
+        if let Some(formatted) = legacy.key(key) {
+            channel.insert_formatted(formatted, item.clone());
+        }
+        if key == "name" {
+            channel.insert("hostname", value(host_name));
+        }
+    }
+    channel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    const LEGACY: &str = r#"# Tunnels (old layout)
+[reconnection]
+max_retries = 3
+
+[[channels]]
+name = "db"            # production database
+host = "db.example.com"
+username = "deploy"
+ports = "5432:5432"
+
+[channels.auth]
+type = "key"
+key_path = "~/.ssh/id_ed25519"
+
+[[channels]]
+name = "cache"
+host = "db.example.com"
+username = "deploy"
+ports = "6379:6379"
+auth = { type = "key", key_path = "~/.ssh/id_ed25519" }
+
+[[channels]]
+name = "bastion"
+host = "bastion.example.com"
+port = 2222
+username = "ops"
+auth = { type = "password", password = "secret" }
+"#;
+
+    #[test]
+    fn test_migrates_legacy_channels() {
+        let mut doc: DocumentMut = LEGACY.parse().unwrap();
+        let report = migrate(&mut doc).unwrap();
+        assert_eq!(report.from_version, 0);
+        assert!(report.is_upgrade());
+
+        let text = doc.to_string();
+        assert!(text.starts_with("version = 1\n"));
+        assert!(text.contains("# production database"));
+
+        let config: AppConfig = toml::from_str(&text).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.reconnection.max_retries, 3);
+
+        let hosts: Vec<(&str, &str, u16)> = config
+            .hosts
+            .iter()
+            .map(|h| (h.name.as_str(), h.host.as_str(), h.port))
+            .collect();
+        assert_eq!(
+            hosts,
+            vec![
+                ("db", "db.example.com", 22),
+                ("bastion", "bastion.example.com", 2222)
+            ]
+        );
+
+        let channels: Vec<(&str, &str)> = config
+            .channels
+            .iter()
+            .map(|c| (c.name.as_str(), c.hostname.primary()))
+            .collect();
+        assert_eq!(channels, vec![("db", "db"), ("cache", "db")]);
+        assert_eq!(config.build_channels().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_current_layout_only_gets_version() {
+        let mut doc: DocumentMut = "[[hosts]]\nname = \"h\"\n".parse().unwrap();
+        let report = migrate(&mut doc).unwrap();
+        assert!(!report.is_upgrade());
+        assert_eq!(report.changes, vec!["set version = 1"]);
+
+        let again = migrate(&mut doc).unwrap();
+        assert!(again.changes.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_newer_version() {
+        let mut doc: DocumentMut = "version = 99\n".parse().unwrap();
+        assert!(migrate(&mut doc).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Application error types
@@ -12,9 +13,15 @@ pub enum AppError {
     #[error("SSH authentication error: {0}")]
     SshAuthentication(String),
 
+    #[error("SSH host key error: {0}")]
+    HostKey(String),
+
     #[error("SSH channel error: {0}")]
     SshChannel(String),
 
+    #[error("Bind error: {0}")]
+    Bind(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -25,4 +32,67 @@ pub enum AppError {
     Service(String),
 }
 
+/// How the reconnect loop treats an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// Network or session failure that may go away: retried with backoff
+    Transient,
+    /// Server rejected the credentials: handled by `reconnection.auth_failure`
+    AuthRejected,
+    /// Invalid config, unreadable key or local address that cannot be bound: not retried
+    Config,
+    /// Server host key could not be verified: not retried
+    HostKeyMismatch,
+}
+
+impl AppError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AppError::Config(_) | AppError::ConfigParse(_) | AppError::Bind(_) => ErrorKind::Config,
+            AppError::SshAuthentication(_) => ErrorKind::AuthRejected,
+            AppError::HostKey(_) => ErrorKind::HostKeyMismatch,
+            AppError::SshConnection(_)
+            | AppError::SshChannel(_)
+            | AppError::Io(_)
+            | AppError::Service(_) => ErrorKind::Transient,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ErrorKind::Transient => "transient",
+            ErrorKind::AuthRejected => "auth-rejected",
+            ErrorKind::Config => "config",
+            ErrorKind::HostKeyMismatch => "host-key-mismatch",
+        };
+        f.write_str(name)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let kind = |e: AppError| e.kind();
+        assert_eq!(
+            kind(AppError::SshConnection("refused".into())),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            kind(AppError::SshAuthentication("rejected".into())),
+            ErrorKind::AuthRejected
+        );
+        assert_eq!(kind(AppError::Bind("in use".into())), ErrorKind::Config);
+        assert_eq!(
+            kind(AppError::HostKey("changed".into())),
+            ErrorKind::HostKeyMismatch
+        );
+    }
+}
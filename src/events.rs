@@ -0,0 +1,141 @@
+//! Live events of the running service (channel state changes, relays), streamed to
+//! `subscribe` IPC clients as JSON lines.
+//!
+//! Like the audit log, the bus is process-wide: [`emit`] is cheap and does nothing
+//! noticeable while nobody is subscribed.
+
+use crate::error::ErrorKind;
+use crate::logging::rfc3339;
+use crate::metrics::RelayRecord;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before a slow one starts missing them
+const CAPACITY: usize = 1024;
+
+static BUS: OnceLock<broadcast::Sender<Event>> = OnceLock::new();
+
+/// One line of the `subscribe` stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    /// When it happened (RFC 3339, UTC)
+    pub timestamp: String,
+    pub channel: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What happened, tagged as `"event"` in the JSON line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum EventKind {
+    /// A session starts connecting to `host`
+    ChannelConnecting {
+        host: String,
+        session: usize,
+    },
+    /// The session is authenticated and forwarding
+    ChannelConnected {
+        host: String,
+        session: usize,
+    },
+    /// An established session ended
+    ChannelDisconnected {
+        host: String,
+        session: usize,
+        reason: String,
+    },
+    /// A connection attempt failed
+    ChannelFailed {
+        host: String,
+        session: usize,
+        kind: ErrorKind,
+        error: String,
+    },
+    /// The session stopped retrying (see `status` for why)
+    ChannelGaveUp {
+        session: usize,
+        failures: u32,
+    },
+    RelayOpened(RelayRecord),
+    /// Carries the final byte counts, duration and close reason
+    RelayClosed(RelayRecord),
+    /// Sent to a subscriber that read too slowly, in place of the `missed` events it lost;
+    /// `channel` is empty
+    Lagged {
+        missed: u64,
+    },
+}
+
+impl Event {
+    /// An event for `channel` stamped with the current time
+    pub fn now(channel: &str, kind: EventKind) -> Self {
+        Self {
+            timestamp: rfc3339(SystemTime::now()),
+            channel: channel.to_string(),
+            kind,
+        }
+    }
+}
+
+fn bus() -> &'static broadcast::Sender<Event> {
+    BUS.get_or_init(|| broadcast::channel(CAPACITY).0)
+}
+
+/// Publish an event for `channel` to every current subscriber
+pub fn emit(channel: &str, kind: EventKind) {
+    let bus = bus();
+    if bus.receiver_count() == 0 {
+        return;
+    }
+    let _ = bus.send(Event::now(channel, kind));
+}
+
+/// Receive every event emitted from now on
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    bus().subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_receives_json_events() {
+        let mut rx = subscribe();
+        emit(
+            "db",
+            EventKind::ChannelFailed {
+                host: "bastion".to_string(),
+                session: 0,
+                kind: ErrorKind::AuthRejected,
+                error: "denied".to_string(),
+            },
+        );
+
+        let event = rx.recv().await.unwrap();
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "channel-failed");
+        assert_eq!(json["channel"], "db");
+        assert_eq!(json["kind"], "auth-rejected");
+
+        let line = serde_json::to_string(&event).unwrap();
+        let back: Event = serde_json::from_str(&line).unwrap();
+        assert!(matches!(
+            back.kind,
+            EventKind::ChannelFailed { session: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn test_lagged_event_is_timestamped() {
+        let event = Event::now("", EventKind::Lagged { missed: 7 });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "lagged");
+        assert_eq!(json["missed"], 7);
+        assert_eq!(json["channel"], "");
+        assert!(json["timestamp"].as_str().is_some_and(|t| t.ends_with('Z')));
+    }
+}
@@ -0,0 +1,189 @@
+//! Application-level health probes, run through a channel's SSH session.
+//!
+//! A probe gets a stream to the channel's destination (opened by the server, exactly like a
+//! tunnelled client connection) and checks that the service behind it answers the way it
+//! should, beyond just accepting the connection.
+
+use crate::config::HealthProbe;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Most a probe reads from the service before deciding
+const MAX_REPLY: usize = 1024;
+
+/// PostgreSQL SSLRequest: length 8, request code 80877103
+const PG_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+
+/// Check the service on `stream`. `host` is the probed host, sent as the HTTP `Host` header.
+/// The error describes what the service did wrong.
+pub async fn probe<S>(probe: &HealthProbe, host: &str, stream: &mut S) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match probe {
+        HealthProbe::Tcp => Ok(()),
+        HealthProbe::Banner { expect } => {
+            let reply = read_reply(stream, expect.len().max(1)).await?;
+            if reply.starts_with(expect.as_bytes()) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "expected banner starting with {:?}, got {:?}",
+                    expect,
+                    first_line(&reply)
+                ))
+            }
+        }
+        HealthProbe::Http {
+            path,
+            expect_status,
+        } => {
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: ssh-channels-hub\r\nConnection: close\r\n\r\n",
+                path, host
+            );
+            send(stream, request.as_bytes()).await?;
+            let reply = read_reply(stream, "HTTP/1.1 200".len()).await?;
+            let line = first_line(&reply);
+            let status = line
+                .strip_prefix("HTTP/")
+                .and_then(|rest| rest.split_whitespace().nth(1))
+                .and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| format!("not an HTTP response: {:?}", line))?;
+            if status == *expect_status {
+                Ok(())
+            } else {
+                Err(format!("expected status {}, got {}", expect_status, status))
+            }
+        }
+        HealthProbe::Postgres => {
+            send(stream, &PG_SSL_REQUEST).await?;
+            match read_reply(stream, 1).await?.first() {
+                Some(b'S') | Some(b'N') => Ok(()),
+                _ => Err("not a PostgreSQL server (no answer to SSLRequest)".to_string()),
+            }
+        }
+        HealthProbe::Redis => {
+            send(stream, b"PING\r\n").await?;
+            let reply = read_reply(stream, 1).await?;
+            match reply.first() {
+                Some(b'+') => Ok(()),
+                // e.g. -NOAUTH: the server is up and speaking RESP
+                Some(b'-') => Ok(()),
+                _ => Err(format!("not a Redis server: {:?}", first_line(&reply))),
+            }
+        }
+        HealthProbe::Mysql => {
+            // Packet header (3-byte length, sequence id), then the protocol version
+            let reply = read_reply(stream, 5).await?;
+            match reply.get(4) {
+                Some(0x0a) => Ok(()),
+                // Error packet: the server answered but refuses us (e.g. too many connections)
+                Some(0xff) => Err(format!(
+                    "MySQL error: {}",
+                    String::from_utf8_lossy(reply.get(7..).unwrap_or_default())
+                )),
+                _ => Err("not a MySQL server (no handshake)".to_string()),
+            }
+        }
+    }
+}
+
+async fn send<S: AsyncWrite + Unpin>(stream: &mut S, data: &[u8]) -> Result<(), String> {
+    stream
+        .write_all(data)
+        .await
+        .and(stream.flush().await)
+        .map_err(|e| format!("send failed: {}", e))
+}
+
+/// Read until at least `min` bytes arrived or the service closed the connection
+async fn read_reply<S: AsyncRead + Unpin>(stream: &mut S, min: usize) -> Result<Vec<u8>, String> {
+    let mut reply = Vec::new();
+    let mut buf = [0u8; MAX_REPLY];
+    while reply.len() < min.min(MAX_REPLY) {
+        let n = stream
+            .read(&mut buf)
+            .await
+            .map_err(|e| format!("read failed: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+    }
+    if reply.is_empty() {
+        return Err("connection closed without a reply".to_string());
+    }
+    Ok(reply)
+}
+
+fn first_line(reply: &[u8]) -> String {
+    let text = String::from_utf8_lossy(reply);
+    text.lines().next().unwrap_or_default().trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    /// Run `probe` against a fake service that reads `expect_request` bytes, then sends `reply`
+    async fn check(
+        probe_: HealthProbe,
+        expect_request: usize,
+        reply: &'static [u8],
+    ) -> Result<(), String> {
+        let (mut client, mut server) = duplex(4096);
+        let service = tokio::spawn(async move {
+            let mut request = vec![0u8; expect_request];
+            server.read_exact(&mut request).await.unwrap();
+            server.write_all(reply).await.unwrap();
+            request
+        });
+        let result = probe(&probe_, "db.internal", &mut client).await;
+        drop(client);
+        service.await.unwrap();
+        result
+    }
+
+    #[tokio::test]
+    async fn test_banner() {
+        let ssh = HealthProbe::Banner {
+            expect: "SSH-".to_string(),
+        };
+        assert!(check(ssh.clone(), 0, b"SSH-2.0-OpenSSH_9.6\r\n")
+            .await
+            .is_ok());
+        let err = check(ssh, 0, b"220 mail ESMTP\r\n").await.unwrap_err();
+        assert!(err.contains("220 mail ESMTP"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_http_status() {
+        let http = |expect_status| HealthProbe::Http {
+            path: "/healthz".to_string(),
+            expect_status,
+        };
+        let request_len = "GET /healthz HTTP/1.1\r\nHost: db.internal\r\nUser-Agent: ssh-channels-hub\r\nConnection: close\r\n\r\n".len();
+        let reply = b"HTTP/1.1 503 Service Unavailable\r\n\r\n";
+        assert!(check(http(503), request_len, reply).await.is_ok());
+        assert_eq!(
+            check(http(200), request_len, reply).await.unwrap_err(),
+            "expected status 200, got 503"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_database_handshakes() {
+        assert!(check(HealthProbe::Postgres, 8, b"N").await.is_ok());
+        assert!(check(HealthProbe::Redis, 6, b"+PONG\r\n").await.is_ok());
+        assert!(check(HealthProbe::Redis, 6, b"HTTP/1.1 400\r\n")
+            .await
+            .is_err());
+        assert!(
+            check(HealthProbe::Mysql, 0, b"\x4a\x00\x00\x00\x0a8.0.36\x00")
+                .await
+                .is_ok()
+        );
+        assert!(check(HealthProbe::Mysql, 0, b"").await.is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Channel lifecycle hooks: shell commands run when a channel comes up, goes down or
+//! fails to connect.
+//!
+//! Each channel with hooks gets a task that follows its events: `on_up` runs when the first
+//! session connects, `on_down` when the last one is lost (not on service shutdown), and
+//! `on_fail` for every failed connection attempt. Hooks of a channel run one at a time, in
+//! order; their output goes to the log.
+
+use crate::config::{ChannelConfig, ChannelTypeParams};
+use crate::error::ErrorKind;
+use crate::events::{Event, EventKind};
+use std::collections::BTreeSet;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hook {
+    Up,
+    Down,
+    Fail,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::Up => "on_up",
+            Hook::Down => "on_down",
+            Hook::Fail => "on_fail",
+        }
+    }
+
+    /// Value of `SSH_HUB_EVENT`
+    fn event(self) -> &'static str {
+        match self {
+            Hook::Up => "up",
+            Hook::Down => "down",
+            Hook::Fail => "fail",
+        }
+    }
+}
+
+/// A hook to run, with its environment
+struct Job {
+    hook: Hook,
+    command: String,
+    env: Vec<(&'static str, String)>,
+}
+
+/// Run the channel's hooks from `events` until cancelled. Subscribe before the channel's
+/// sessions start so no event is missed.
+pub async fn run(
+    config: ChannelConfig,
+    mut events: broadcast::Receiver<Event>,
+    cancel: CancellationToken,
+) {
+    let (queue, mut jobs) = mpsc::unbounded_channel::<Job>();
+    let channel = config.clone();
+    tokio::spawn(async move {
+        while let Some(job) = jobs.recv().await {
+            execute(&channel, job).await;
+        }
+    });
+
+    // Sessions currently connected; the channel is up while any is
+    let mut up = BTreeSet::new();
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => event,
+        };
+        let event = match event {
+            Ok(event) if event.channel == config.name => event,
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!(channel = %config.name, missed, "Hook runner fell behind; some hooks may not have run");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let (hook, host, session, error) = match event.kind {
+            EventKind::ChannelConnected { host, session } => {
+                let first = up.is_empty();
+                up.insert(session);
+                if !first {
+                    continue;
+                }
+                (Hook::Up, host, session, None)
+            }
+            EventKind::ChannelDisconnected {
+                host,
+                session,
+                reason,
+            } => {
+                if !up.remove(&session) || !up.is_empty() {
+                    continue;
+                }
+                (Hook::Down, host, session, Some((reason, None)))
+            }
+            EventKind::ChannelFailed {
+                host,
+                session,
+                kind,
+                error,
+            } => (Hook::Fail, host, session, Some((error, Some(kind)))),
+            _ => continue,
+        };
+        let command = match hook {
+            Hook::Up => &config.hooks.on_up,
+            Hook::Down => &config.hooks.on_down,
+            Hook::Fail => &config.hooks.on_fail,
+        };
+        if let Some(command) = command {
+            let env = hook_env(&config, hook, &host, session, error);
+            let _ = queue.send(Job {
+                hook,
+                command: command.clone(),
+                env,
+            });
+        }
+    }
+}
+
+/// Environment describing the event to the hook command
+fn hook_env(
+    config: &ChannelConfig,
+    hook: Hook,
+    host: &str,
+    session: usize,
+    error: Option<(String, Option<ErrorKind>)>,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("SSH_HUB_EVENT", hook.event().to_string()),
+        ("SSH_HUB_CHANNEL", config.name.clone()),
+        ("SSH_HUB_CHANNEL_TYPE", config.channel_type.clone()),
+        ("SSH_HUB_HOST", host.to_string()),
+        ("SSH_HUB_SESSION", session.to_string()),
+    ];
+    if let Some(endpoint) = config.hosts.iter().find(|h| h.name == host) {
+        env.push(("SSH_HUB_HOST_ADDR", endpoint.host.clone()));
+        env.push(("SSH_HUB_HOST_PORT", endpoint.port.to_string()));
+        env.push(("SSH_HUB_USER", endpoint.username.clone()));
+    }
+    match &config.params {
+        ChannelTypeParams::DirectTcpIp {
+            listen_host,
+            local_port,
+            dest_host,
+            dest_port,
+        } => {
+            env.push(("SSH_HUB_LOCAL_HOST", listen_host.clone()));
+            env.push(("SSH_HUB_LOCAL_PORT", local_port.to_string()));
+            env.push(("SSH_HUB_REMOTE_HOST", dest_host.clone()));
+            env.push(("SSH_HUB_REMOTE_PORT", dest_port.to_string()));
+        }
+        ChannelTypeParams::ForwardedTcpIp {
+            remote_bind_port,
+            local_connect_host,
+            local_connect_port,
+        } => {
+            env.push(("SSH_HUB_LOCAL_HOST", local_connect_host.clone()));
+            env.push(("SSH_HUB_LOCAL_PORT", local_connect_port.to_string()));
+            env.push(("SSH_HUB_REMOTE_PORT", remote_bind_port.to_string()));
+        }
+        ChannelTypeParams::Dynamic {
+            listen_host,
+            local_port,
+        } => {
+            env.push(("SSH_HUB_LOCAL_HOST", listen_host.clone()));
+            env.push(("SSH_HUB_LOCAL_PORT", local_port.to_string()));
+        }
+        ChannelTypeParams::Session { .. } => {}
+    }
+    if let Some((error, kind)) = error {
+        env.push(("SSH_HUB_ERROR", error));
+        if let Some(kind) = kind {
+            env.push(("SSH_HUB_ERROR_KIND", kind.to_string()));
+        }
+    }
+    env
+}
+
+/// Run one hook through the shell, logging its output, and kill it at the timeout
+async fn execute(config: &ChannelConfig, job: Job) {
+    let hook = job.hook.name();
+    let mut command = shell(&job.command);
+    command
+        .envs(job.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    info!(channel = %config.name, hook = %hook, command = %job.command, "Running hook");
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!(channel = %config.name, hook = %hook, error = %e, "Failed to start hook");
+            return;
+        }
+    };
+    let output = match tokio::time::timeout(config.hooks.timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            warn!(channel = %config.name, hook = %hook, error = %e, "Hook failed");
+            return;
+        }
+        Err(_) => {
+            warn!(
+                channel = %config.name,
+                hook = %hook,
+                "Hook still running after {}s, killed",
+                config.hooks.timeout.as_secs()
+            );
+            return;
+        }
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!(channel = %config.name, hook = %hook, "{}", line);
+    }
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        warn!(channel = %config.name, hook = %hook, "{}", line);
+    }
+    if output.status.success() {
+        debug!(channel = %config.name, hook = %hook, "Hook finished");
+    } else {
+        warn!(channel = %config.name, hook = %hook, status = %output.status, "Hook failed");
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::events;
+    use std::time::Duration;
+
+    fn channel(extra: &str) -> ChannelConfig {
+        let toml = format!(
+            r#"
+[hooks]
+on_fail = "echo default"
+
+[[hosts]]
+name = "bastion"
+host = "bastion.example.com"
+port = 2222
+username = "deploy"
+auth = {{ type = "password", password = "secret" }}
+
+[[channels]]
+name = "hooks-db"
+hostname = "bastion"
+ports = "15432:5432"
+{}
+"#,
+            extra
+        );
+        let config: AppConfig = toml::from_str(&toml).unwrap();
+        config.build_channels().unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_channel_overrides_default_hooks() {
+        let hooks = channel("on_up = \"./check.sh\"").hooks;
+        assert_eq!(hooks.on_up.as_deref(), Some("./check.sh"));
+        assert_eq!(hooks.on_fail.as_deref(), Some("echo default"));
+        assert_eq!(hooks.timeout, Duration::from_secs(30));
+
+        let hooks = channel("on_fail = \"\"").hooks;
+        assert!(hooks.is_empty());
+    }
+
+    #[test]
+    fn test_hook_env() {
+        let config = channel("");
+        let env = hook_env(
+            &config,
+            Hook::Fail,
+            "bastion",
+            0,
+            Some(("refused".to_string(), Some(ErrorKind::Transient))),
+        );
+        let get = |key: &str| env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("SSH_HUB_EVENT"), Some("fail"));
+        assert_eq!(get("SSH_HUB_CHANNEL"), Some("hooks-db"));
+        assert_eq!(get("SSH_HUB_HOST_ADDR"), Some("bastion.example.com"));
+        assert_eq!(get("SSH_HUB_HOST_PORT"), Some("2222"));
+        assert_eq!(get("SSH_HUB_LOCAL_PORT"), Some("15432"));
+        assert_eq!(get("SSH_HUB_REMOTE_PORT"), Some("5432"));
+        assert_eq!(get("SSH_HUB_ERROR"), Some("refused"));
+        assert_eq!(get("SSH_HUB_ERROR_KIND"), Some("transient"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_up_and_down_run_once_per_channel_transition() {
+        let out = std::env::temp_dir().join(format!("hooks-test-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&out);
+        let config = channel(&format!(
+            "on_up = 'echo up $SSH_HUB_SESSION >> {0}'\non_down = 'echo down $SSH_HUB_ERROR >> {0}'",
+            out.display()
+        ));
+        let cancel = CancellationToken::new();
+        tokio::spawn(run(config, events::subscribe(), cancel.clone()));
+
+        let connected = |session| EventKind::ChannelConnected {
+            host: "bastion".to_string(),
+            session,
+        };
+        let disconnected = |session| EventKind::ChannelDisconnected {
+            host: "bastion".to_string(),
+            session,
+            reason: "lost".to_string(),
+        };
+        events::emit("hooks-db", connected(0));
+        events::emit("hooks-db", connected(1));
+        events::emit("other", disconnected(0));
+        events::emit("hooks-db", disconnected(0));
+        events::emit("hooks-db", disconnected(1));
+
+        let mut content = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            content = std::fs::read_to_string(&out).unwrap_or_default();
+            if content.lines().count() >= 2 {
+                break;
+            }
+        }
+        cancel.cancel();
+        let _ = std::fs::remove_file(&out);
+        assert_eq!(content, "up 0\ndown lost\n");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::error::{AppError, Result};
+use std::collections::BTreeMap;
+use toml_edit::{DocumentMut, Item, Value};
+
+/// Top-level tables whose string values are interpolated
+const INTERPOLATED_TABLES: [&str; 2] = ["hosts", "channels"];
+
+/// Expand `${VAR}` and `${VAR:-default}` in `s`. `vars` are looked up first, then the
+/// environment. `$${` produces a literal `${`; any other `$` is kept as is.
+/// Unknown variables without a default are an error.
+pub fn interpolate(s: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(tail) = after.strip_prefix("${") {
+            out.push_str("${");
+            rest = tail;
+        } else if let Some(body) = after.strip_prefix('{') {
+            let end = body.find('}').ok_or_else(|| {
+                AppError::Config(format!("Unterminated '${{' in config value '{}'", s))
+            })?;
+            let expr = &body[..end];
+            let (name, default) = match expr.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (expr, None),
+            };
+            if name.is_empty() {
+                return Err(AppError::Config(format!(
+                    "Empty variable name in config value '{}'",
+                    s
+                )));
+            }
+
+            let value = vars
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+                .filter(|v| default.is_none() || !v.is_empty())
+                .or_else(|| default.map(str::to_string))
+                .ok_or_else(|| {
+                    AppError::Config(format!(
+                        "Undefined variable '{}' (define it in [vars] or the environment, or use ${{{}:-default}})",
+                        name, name
+                    ))
+                })?;
+            out.push_str(&value);
+            rest = &body[end + 1..];
+        } else {
+            out.push('$');
+            rest = after;
+        }
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Read the `[vars]` table; its values may reference environment variables
+pub fn read_vars(doc: &DocumentMut) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+    let Some(table) = doc.get("vars").and_then(Item::as_table_like) else {
+        return Ok(vars);
+    };
+
+    for (key, item) in table.iter() {
+        let raw = item
+            .as_str()
+            .ok_or_else(|| AppError::Config(format!("vars.{} must be a string", key)))?;
+        let value = interpolate(raw, &BTreeMap::new())
+            .map_err(|e| AppError::Config(format!("vars.{}: {}", key, e)))?;
+        vars.insert(key.to_string(), value);
+    }
+
+    Ok(vars)
+}
+
+/// Interpolate every string in `[[hosts]]` and `[[channels]]`, keeping formatting and line numbers
+pub fn interpolate_document(doc: &mut DocumentMut, vars: &BTreeMap<String, String>) -> Result<()> {
+    for key in INTERPOLATED_TABLES {
+        if let Some(item) = doc.get_mut(key) {
+            interpolate_item(item, vars, key)?;
+        }
+    }
+    Ok(())
+}
+
+fn interpolate_item(item: &mut Item, vars: &BTreeMap<String, String>, path: &str) -> Result<()> {
+    match item {
+        Item::Value(value) => interpolate_value(value, vars, path),
+        Item::Table(table) => {
+            for (key, child) in table.iter_mut() {
+                interpolate_item(child, vars, &format!("{}.{}", path, key))?;
+            }
+            Ok(())
+        }
+        Item::ArrayOfTables(tables) => {
+            for (i, table) in tables.iter_mut().enumerate() {
+                for (key, child) in table.iter_mut() {
+                    interpolate_item(child, vars, &format!("{}[{}].{}", path, i, key))?;
+                }
+            }
+            Ok(())
+        }
+        Item::None => Ok(()),
+    }
+}
+
+fn interpolate_value(value: &mut Value, vars: &BTreeMap<String, String>, path: &str) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            let original = s.value();
+            if !original.contains('$') {
+                return Ok(());
+            }
+            let expanded = interpolate(original, vars)
+                .map_err(|e| AppError::Config(format!("{}: {}", path, e)))?;
+            if expanded != *original {
+                let decor = value.decor().clone();
+                *value = Value::from(expanded);
+                *value.decor_mut() = decor;
+            }
+            Ok(())
+        }
+        Value::Array(array) => {
+            for (i, v) in array.iter_mut().enumerate() {
+                interpolate_value(v, vars, &format!("{}[{}]", path, i))?;
+            }
+            Ok(())
+        }
+        Value::InlineTable(table) => {
+            for (key, v) in table.iter_mut() {
+                interpolate_value(v, vars, &format!("{}.{}", path, key))?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> BTreeMap<String, String> {
+        let mut vars = BTreeMap::new();
+        vars.insert("USER_NAME".to_string(), "alice".to_string());
+        vars.insert("EMPTY".to_string(), String::new());
+        vars
+    }
+
+    #[test]
+    fn test_interpolate_vars_and_defaults() {
+        assert_eq!(interpolate("${USER_NAME}", &vars()).unwrap(), "alice");
+        assert_eq!(
+            interpolate("/home/${USER_NAME}/.ssh/id", &vars()).unwrap(),
+            "/home/alice/.ssh/id"
+        );
+        assert_eq!(
+            interpolate("${SCH_SURELY_UNSET_VAR:-fallback}", &vars()).unwrap(),
+            "fallback"
+        );
+        assert_eq!(
+            interpolate("${EMPTY:-fallback}", &vars()).unwrap(),
+            "fallback"
+        );
+        assert_eq!(interpolate("${EMPTY}", &vars()).unwrap(), "");
+        assert_eq!(interpolate("pa$$word $x", &vars()).unwrap(), "pa$$word $x");
+        assert_eq!(interpolate("$${HOME}", &vars()).unwrap(), "${HOME}");
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        assert!(interpolate("${SCH_SURELY_UNSET_VAR}", &vars()).is_err());
+        assert!(interpolate("${USER_NAME", &vars()).is_err());
+        assert!(interpolate("${}", &vars()).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_document_keeps_layout() {
+        let mut doc: DocumentMut = r#"
+[vars]
+port = "2222"
+
+[[hosts]]
+name = "h"
+host = "example.com"   # comment
+username = "${USER_NAME}"
+auth = { type = "key", key_path = "/keys/${USER_NAME}" }
+
+[[channels]]
+name = "c"
+hostname = "h"
+ports = "${LOCAL:-8080}:80"
+"#
+        .parse()
+        .unwrap();
+
+        let mut all = read_vars(&doc).unwrap();
+        all.extend(vars());
+        interpolate_document(&mut doc, &all).unwrap();
+        let text = doc.to_string();
+
+        assert!(text.contains("username = \"alice\""));
+        assert!(text.contains("key_path = \"/keys/alice\""));
+        assert!(text.contains("ports = \"8080:80\""));
+        assert!(text.contains("host = \"example.com\"   # comment"));
+    }
+}
@@ -1,7 +1,20 @@
+pub mod audit;
 pub mod cli;
 pub mod config;
+pub mod config_format;
+pub mod config_merge;
+pub mod config_migrate;
 pub mod error;
+pub mod events;
+pub mod health;
+pub mod hooks;
+pub mod interpolate;
+pub mod logging;
+pub mod metrics;
 pub mod port_check;
+pub mod retry;
 pub mod service;
+pub mod socks;
 pub mod ssh;
 pub mod ssh_config;
+pub mod validate;
@@ -0,0 +1,241 @@
+//! Log files: a rotating file writer shared by the service log and the audit log,
+//! and the line filtering behind the `logs` command.
+
+use crate::error::Result;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// When a [`RotatingFile`] moves its contents aside
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Never,
+    /// Once the file would grow past this many bytes
+    Size(u64),
+    /// On the first write after midnight UTC
+    Daily,
+}
+
+/// Appends records to `path`, moving it to `path.1` (and older files up to
+/// `path.<max_files>`) when `rotation` says so. Each `write` is one record and is
+/// never split across files.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    max_files: usize,
+    file: File,
+    size: u64,
+    /// UTC day (days since the epoch) the current file was last written on
+    day: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, rotation: Rotation, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        let day = metadata
+            .modified()
+            .map(utc_day)
+            .unwrap_or_else(|_| utc_day(SystemTime::now()));
+        Ok(Self {
+            path,
+            rotation,
+            max_files,
+            file,
+            size: metadata.len(),
+            day,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn due(&self, len: u64, today: u64) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        match self.rotation {
+            Rotation::Never => false,
+            Rotation::Size(max) => self.size + len > max,
+            Rotation::Daily => today != self.day,
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+            self.size = 0;
+            return Ok(());
+        }
+        let _ = std::fs::remove_file(self.rotated(self.max_files));
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let today = utc_day(SystemTime::now());
+        if self.due(buf.len() as u64, today) {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        self.day = today;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn utc_day(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86_400
+}
+
+/// Format `time` as RFC 3339 in UTC with millisecond precision
+pub fn rfc3339(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        since.subsec_millis()
+    )
+}
+
+/// Resolve a log file path from the config: `~` is expanded and relative paths are
+/// taken from `base_dir` (the config file's directory)
+pub fn resolve_path(path: &Path, base_dir: &Path) -> Result<PathBuf> {
+    if path.is_relative() && !path.starts_with("~") {
+        Ok(base_dir.join(path))
+    } else {
+        crate::ssh_config::expand_tilde(path)
+    }
+}
+
+/// Whether a log line (text or JSON format) was logged for `channel`
+pub fn line_matches_channel(line: &str, channel: &str) -> bool {
+    if line.starts_with('{') {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(line) {
+            return value["fields"]["channel"] == channel || value["channel"] == channel;
+        }
+    }
+    let field = format!("channel={}", channel);
+    line.split_whitespace().any(|word| word == field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn lines(path: &Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn test_size_rotation() {
+        let dir = temp_dir("rotate-size");
+        let path = dir.join("hub.log");
+        let record = b"0123456789\n";
+
+        let mut file = RotatingFile::open(path.clone(), Rotation::Size(22), 2).unwrap();
+        for _ in 0..7 {
+            file.write_all(record).unwrap();
+        }
+
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&dir.join("hub.log.1")), 2);
+        assert_eq!(lines(&dir.join("hub.log.2")), 2);
+        assert!(!dir.join("hub.log.3").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        let dir = temp_dir("rotate-daily");
+        let path = dir.join("hub.log");
+
+        let mut file = RotatingFile::open(path.clone(), Rotation::Daily, 3).unwrap();
+        file.write_all(b"today\n").unwrap();
+        file.write_all(b"still today\n").unwrap();
+        assert!(!dir.join("hub.log.1").exists());
+
+        file.day -= 1;
+        file.write_all(b"tomorrow\n").unwrap();
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&dir.join("hub.log.1")), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let t = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(rfc3339(t), "2024-02-29T12:34:56.789Z");
+    }
+
+    #[test]
+    fn test_line_matches_channel() {
+        let text =
+            "2026-10-18T08:00:00.000000Z  INFO SSH session established channel=db host=bastion";
+        assert!(line_matches_channel(text, "db"));
+        assert!(!line_matches_channel(text, "d"));
+        assert!(!line_matches_channel(text, "web"));
+
+        let json = r#"{"timestamp":"2026-10-18T08:00:00Z","level":"INFO","fields":{"message":"Relay ended","channel":"web"}}"#;
+        assert!(line_matches_channel(json, "web"));
+        assert!(!line_matches_channel(json, "db"));
+    }
+}
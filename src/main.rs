@@ -1,41 +1,67 @@
+mod audit;
 mod cli;
 mod config;
+mod config_format;
+mod config_merge;
+mod config_migrate;
 mod error;
+mod events;
+mod health;
+mod hooks;
+mod interpolate;
+mod logging;
+mod metrics;
 mod port_check;
+mod retry;
 mod service;
+mod socks;
 mod ssh;
 mod ssh_config;
+mod validate;
 
 use anyhow::{Context as AnyhowContext, Result as AnyhowResult};
 use clap::Parser;
 use cli::{Cli, Commands};
-use config::AppConfig;
-use port_check::{test_port_connection, test_tunnel_connection};
+use config::{AppConfig, LogFormat, LogRotation, LoggingConfig};
+use config_format::ConfigFormat;
 use service::{ServiceManager, ServiceState};
-use ssh_config::{default_ssh_config_path, parse_ssh_config};
+use ssh_config::{alias_matches, default_ssh_config_path, parse_ssh_config};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::Arc;
 
+use logging::{RotatingFile, Rotation};
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 #[tokio::main]
 async fn main() -> AnyhowResult<()> {
     let cli = Cli::parse();
 
-    // Initialize logging
-    init_logging(cli.debug)?;
-
     // Determine config path
     let config_path = cli.config.clone().unwrap_or_else(AppConfig::default_path);
 
+    // The running service (foreground, or the daemon's child) also honours [logging];
+    // a config that fails to load is reported by handle_start
+    let logging = match &cli.command {
+        Commands::Start { daemon: false } => AppConfig::from_file(&config_path)
+            .ok()
+            .and_then(|c| c.logging),
+        _ => None,
+    };
+
+    // Initialize logging
+    init_logging(cli.debug, logging.as_ref(), &config_path)?;
+
     // Handle commands
     match cli.command {
         Commands::Start { daemon } => {
@@ -50,16 +76,57 @@ async fn main() -> AnyhowResult<()> {
         Commands::Status => {
             handle_status(config_path).await?;
         }
-        Commands::Validate { config } => {
+        Commands::Connections { channel } => {
+            handle_connections(config_path, &channel).await?;
+        }
+        Commands::Events => {
+            handle_events(config_path).await?;
+        }
+        Commands::Logs {
+            follow,
+            channel,
+            lines,
+        } => {
+            handle_logs(config_path, follow, channel.as_deref(), lines).await?;
+        }
+        Commands::Validate {
+            config,
+            show_resolved,
+        } => {
             let path = config.or(Some(config_path));
-            handle_validate(path).await?;
+            handle_validate(path, show_resolved).await?;
+        }
+        Commands::Generate {
+            ssh_config,
+            output,
+            hosts,
+            merge,
+            format,
+        } => {
+            handle_generate(ssh_config, output, hosts, merge, format).await?;
+        }
+        Commands::Convert {
+            input,
+            output,
+            format,
+        } => {
+            handle_convert(input, output, format)?;
+        }
+        Commands::Migrate { config, dry_run } => {
+            handle_migrate(config.unwrap_or(config_path), dry_run)?;
         }
-        Commands::Generate { ssh_config, output } => {
-            handle_generate(ssh_config, output).await?;
+        Commands::Schema { output } => {
+            handle_schema(output)?;
         }
-        Commands::Test { config } => {
-            let test_config_path = config.unwrap_or_else(AppConfig::default_path);
-            handle_test(test_config_path).await?;
+        Commands::Test {
+            channel,
+            config,
+            json,
+        } => {
+            let code = handle_test(config.unwrap_or(config_path), channel.as_deref(), json).await?;
+            if code != 0 {
+                std::process::exit(code);
+            }
         }
     }
 
@@ -94,17 +161,48 @@ async fn spawn_daemon(config_path: &Path, debug: bool) -> AnyhowResult<()> {
     Ok(())
 }
 
-/// Initialize logging subsystem
-fn init_logging(debug: bool) -> AnyhowResult<()> {
+/// Initialize logging subsystem: stderr, plus the `[logging] file` when one is configured.
+/// `--debug` wins over `RUST_LOG`, which wins over `[logging] level`.
+fn init_logging(
+    debug: bool,
+    logging: Option<&LoggingConfig>,
+    config_path: &Path,
+) -> AnyhowResult<()> {
+    let level = logging.map_or("info", |l| l.level.as_str());
     let filter = if debug {
         EnvFilter::new("debug")
     } else {
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level))
     };
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
+    let file_layer = match logging.and_then(|l| l.file.as_ref().map(|file| (l, file))) {
+        Some((logging, file)) => {
+            let path = logging::resolve_path(file, &run_dir(config_path))?;
+            let rotation = match logging.rotation {
+                LogRotation::Size => {
+                    Rotation::Size(logging.max_size_mb.saturating_mul(1024 * 1024))
+                }
+                LogRotation::Daily => Rotation::Daily,
+                LogRotation::Never => Rotation::Never,
+            };
+            let writer = RotatingFile::open(path.clone(), rotation, logging.max_files)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(std::sync::Mutex::new(writer))
+                .with_ansi(false)
+                .with_target(false);
+            Some(match logging.format {
+                LogFormat::Text => layer.boxed(),
+                LogFormat::Json => layer.json().boxed(),
+            })
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .with(file_layer)
         .init();
 
     Ok(())
@@ -127,6 +225,21 @@ async fn handle_start(
 
     info!("Configuration loaded successfully");
 
+    // Bind before starting channels so a taken metrics address fails fast
+    let metrics_listener = match &config.metrics {
+        Some(metrics) => Some(
+            TcpListener::bind(&metrics.listen)
+                .await
+                .with_context(|| format!("Failed to bind metrics endpoint {}", metrics.listen))?,
+        ),
+        None => None,
+    };
+
+    if let Some(audit_config) = &config.audit {
+        let base_dir = config_path.parent().unwrap_or_else(|| Path::new("."));
+        audit::init(audit_config, base_dir).context("Failed to open audit log")?;
+    }
+
     let service_manager = Arc::new(ServiceManager::new(config));
 
     // Start the service
@@ -146,6 +259,14 @@ async fn handle_start(
         port
     );
 
+    if let Some(listener) = metrics_listener {
+        tokio::spawn(metrics::serve(
+            listener,
+            Arc::clone(&service_manager),
+            cancel.clone(),
+        ));
+    }
+
     info!("Service running in foreground. Press Ctrl+C to stop.");
 
     tokio::select! {
@@ -213,10 +334,13 @@ fn status_to_toml(status: &service::ServiceStatus) -> String {
         ServiceState::Stopping => "Stopping",
         ServiceState::Error(_) => "Error",
     };
-    format!(
-        "state = \"{}\"\nactive_channels = {}\ntotal_channels = {}",
-        state_str, status.active_channels, status.total_channels
-    )
+    let response = StatusResponse {
+        state: state_str.to_string(),
+        active_channels: status.active_channels,
+        total_channels: status.total_channels,
+        channels: status.channels.clone(),
+    };
+    toml::to_string(&response).unwrap_or_default()
 }
 
 /// Bind TCP on 127.0.0.1:0, write port to file, spawn task that accepts connections and responds with current status.
@@ -287,48 +411,112 @@ async fn read_line_async(stream: &mut TcpStream) -> AnyhowResult<String> {
     Ok(String::from_utf8(buf).unwrap_or_default())
 }
 
-/// Handle one IPC connection: read command line ("status" or "stop"). "stop" -> cancel shutdown and reply "ok"; else -> reply status TOML.
+/// Handle one IPC connection: read command line ("status", "stop", "connections <channel>" or "subscribe").
+/// "stop" -> cancel shutdown and reply "ok"; "connections" -> reply relays TOML;
+/// "subscribe" -> stream events as JSON lines; else -> reply status TOML.
 async fn handle_ipc_connection(
     mut stream: TcpStream,
     service_manager: Arc<ServiceManager>,
     shutdown: CancellationToken,
 ) -> AnyhowResult<()> {
-    let cmd = read_line_async(&mut stream).await?.trim().to_lowercase();
-    if cmd == "stop" {
-        shutdown.cancel();
-        stream.write_all(b"ok\n").await?;
-        stream.shutdown().await?;
-        return Ok(());
-    }
-    let status = service_manager.status().await;
-    let body = status_to_toml(&status);
+    let line = read_line_async(&mut stream).await?;
+    let (cmd, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+    let body = match cmd.to_lowercase().as_str() {
+        "subscribe" => return stream_events(stream, shutdown).await,
+        "stop" => {
+            shutdown.cancel();
+            "ok\n".to_string()
+        }
+        "connections" => {
+            let channel = arg.trim();
+            let response = match service_manager.connections(channel).await {
+                Some(connections) => ConnectionsResponse {
+                    error: None,
+                    connections: Some(connections),
+                },
+                None => ConnectionsResponse {
+                    error: Some(format!("No running channel named '{}'", channel)),
+                    connections: None,
+                },
+            };
+            toml::to_string(&response).unwrap_or_default()
+        }
+        _ => status_to_toml(&service_manager.status().await),
+    };
     stream.write_all(body.as_bytes()).await?;
     stream.shutdown().await?;
     Ok(())
 }
 
-/// Read port file (plain text) and connect to main process to fetch status.
-async fn query_status_via_ipc(config_path: &Path) -> AnyhowResult<service::ServiceStatus> {
+/// Serve a "subscribe" request: write each event as a JSON line until the client
+/// disconnects or the service stops
+async fn stream_events(stream: TcpStream, shutdown: CancellationToken) -> AnyhowResult<()> {
+    let mut events = events::subscribe();
+    let (mut reader, mut writer) = stream.into_split();
+    let mut probe = [0u8; 1];
+    loop {
+        let line = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            // Subscribers send nothing after the request: EOF or an error means they left
+            _ = reader.read(&mut probe) => break,
+            event = events.recv() => match event {
+                Ok(event) => serde_json::to_string(&event)?,
+                Err(broadcast::error::RecvError::Lagged(missed)) => serde_json::to_string(
+                    &events::Event::now("", events::EventKind::Lagged { missed }),
+                )?,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        writer.write_all(format!("{}\n", line).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Connect to the running service's IPC listener (port from the port file)
+async fn connect_ipc(config_path: &Path) -> AnyhowResult<TcpStream> {
     let port_path = port_file_path(config_path);
     let content =
         std::fs::read_to_string(&port_path).context("Read port file (is service running?)")?;
     let port: u16 = content.trim().parse().context("Parse port file")?;
-    let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port))
+    TcpStream::connect(format!("127.0.0.1:{}", port))
         .await
-        .context("Connect to service (is it running?)")?;
-    stream.write_all(b"status\n").await?;
+        .context("Connect to service (is it running?)")
+}
+
+/// Send one request line to the running service and read the whole reply
+async fn ipc_request(config_path: &Path, request: &str) -> AnyhowResult<String> {
+    let mut stream = connect_ipc(config_path).await?;
+    stream
+        .write_all(format!("{}\n", request).as_bytes())
+        .await?;
     stream.shutdown().await?;
     let mut buf = Vec::new();
     stream.read_to_end(&mut buf).await?;
-    let body = String::from_utf8(buf).context("IPC response not UTF-8")?;
+    String::from_utf8(buf).context("IPC response not UTF-8")
+}
+
+/// Read port file (plain text) and connect to main process to fetch status.
+async fn query_status_via_ipc(config_path: &Path) -> AnyhowResult<service::ServiceStatus> {
+    let body = ipc_request(config_path, "status").await?;
     parse_status_toml(&body).context("Parse status response")
 }
 
-#[derive(serde::Deserialize)]
+/// Reply to the "connections <channel>" IPC request
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ConnectionsResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    connections: Option<metrics::ChannelConnections>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 struct StatusResponse {
     state: String,
     active_channels: usize,
     total_channels: usize,
+    #[serde(default)]
+    channels: Vec<ssh::ChannelStatus>,
 }
 
 fn parse_status_toml(s: &str) -> AnyhowResult<service::ServiceStatus> {
@@ -345,18 +533,13 @@ fn parse_status_toml(s: &str) -> AnyhowResult<service::ServiceStatus> {
         state,
         active_channels: r.active_channels,
         total_channels: r.total_channels,
+        channels: r.channels,
     })
 }
 
 /// Send "stop" via IPC so daemon exits gracefully; then remove run files.
 async fn send_stop_via_ipc(config_path: &Path) -> AnyhowResult<()> {
-    let port_path = port_file_path(config_path);
-    let content =
-        std::fs::read_to_string(&port_path).context("Read port file (is service running?)")?;
-    let port: u16 = content.trim().parse().context("Parse port file")?;
-    let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port))
-        .await
-        .context("Connect to service (is it running?)")?;
+    let mut stream = connect_ipc(config_path).await?;
     stream.write_all(b"stop\n").await?;
     stream.shutdown().await?;
     let mut buf = vec![0u8; 8];
@@ -410,19 +593,67 @@ async fn handle_restart(config_path: std::path::PathBuf, debug: bool) -> AnyhowR
     Ok(())
 }
 
-/// Print channel list from config (name, local -> dest or remote -> local).
-fn print_channel_list(channels: &[config::ConnectionConfig]) {
+/// Live channel state for the channel list, e.g. "  [reconnecting, 2 failure(s)]".
+fn channel_state_suffix(live: &[ssh::ChannelStatus], c: &config::ConnectionConfig) -> String {
+    let Some(status) = live.iter().find(|s| s.name == c.name) else {
+        return String::new();
+    };
+    let via = match &status.active_host {
+        Some(host) if c.hostname.has_fallbacks() => format!(" via {}", host),
+        _ => String::new(),
+    };
+    let mut sessions = if status.sessions > 1 {
+        format!(", {}/{} sessions", status.sessions_up, status.sessions)
+    } else {
+        String::new()
+    };
+    if let Some(health) = status
+        .health
+        .as_ref()
+        .filter(|_| status.state == ssh::ChannelState::Connected)
+    {
+        sessions.push_str(if health.healthy {
+            ", healthy"
+        } else {
+            ", unhealthy"
+        });
+    }
+    match status.state {
+        ssh::ChannelState::Reconnecting | ssh::ChannelState::GaveUp if status.failures > 0 => {
+            format!(
+                "  [{}{}, {} failure(s){}]",
+                status.state, via, status.failures, sessions
+            )
+        }
+        state => format!("  [{}{}{}]", state, via, sessions),
+    }
+}
+
+/// Print channel list from config (name, local -> dest or remote -> local), with live state if known.
+fn print_channel_list(channels: &[config::ConnectionConfig], live: &[ssh::ChannelStatus]) {
     if channels.is_empty() {
         return;
     }
     println!("  Channels:");
     for c in channels {
+        let state = channel_state_suffix(live, c);
         let is_remote = c
             .channel_type
             .as_deref()
             .map(|t| t == "forwarded-tcpip")
             .unwrap_or(false);
-        if is_remote {
+        let is_dynamic = c.channel_type.as_deref() == Some("dynamic");
+        if is_dynamic {
+            let local = c
+                .ports
+                .local_port
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            println!(
+                "    - {} \tsocks5 {:>5} -> * (host: {}){}",
+                c.name, local, c.hostname, state
+            );
+        } else if is_remote {
             // forwarded-tcpip: ports = "local:remote" -> remote bind port = dest_port, local connect = dest_host:local_port
             let remote = c.ports.dest_port.to_string();
             let local_dest = format!(
@@ -434,8 +665,8 @@ fn print_channel_list(channels: &[config::ConnectionConfig]) {
                     .unwrap_or_else(|| "?".to_string())
             );
             println!(
-                "    - {} \tremote {:>5} -> local {} (host: {})",
-                c.name, remote, local_dest, c.hostname
+                "    - {} \tremote {:>5} -> local {} (host: {}){}",
+                c.name, remote, local_dest, c.hostname, state
             );
         } else {
             let local = c
@@ -445,10 +676,28 @@ fn print_channel_list(channels: &[config::ConnectionConfig]) {
                 .unwrap_or_else(|| "?".to_string());
             let dest = format!("{}:{}", c.dest_host, c.ports.dest_port);
             println!(
-                "    - {} \tlisten {:>5} -> {} (host: {})",
-                c.name, local, dest, c.hostname
+                "    - {} \tlisten {:>5} -> {} (host: {}){}",
+                c.name, local, dest, c.hostname, state
             );
         }
+        if let Some(status) = live.iter().find(|s| s.name == c.name) {
+            if let Some(reason) = &status.reason {
+                let kind = status.error_kind.map(|k| k.to_string()).unwrap_or_default();
+                println!("        last error ({}): {}", kind, reason);
+            }
+            if let Some(health) = &status.health {
+                match &health.error {
+                    Some(error) => println!(
+                        "        health ({}): failing, {} in a row: {}",
+                        health.probe, health.failures, error
+                    ),
+                    None => println!(
+                        "        health ({}): ok in {} ms",
+                        health.probe, health.latency_ms
+                    ),
+                }
+            }
+        }
     }
 }
 
@@ -481,7 +730,7 @@ async fn handle_status(config_path: PathBuf) -> AnyhowResult<()> {
             }
         }
         if let Ok(config) = AppConfig::from_file(&config_path) {
-            print_channel_list(&config.channels);
+            print_channel_list(&config.channels, &status.channels);
         }
         return Ok(());
     }
@@ -500,7 +749,7 @@ async fn handle_status(config_path: PathBuf) -> AnyhowResult<()> {
             println!("  Active Channels: 0/{}", total);
             println!("  Config: {}", config_path.display());
             println!("  Note: Service is not running. Start with: ssh-channels-hub start");
-            print_channel_list(&config.channels);
+            print_channel_list(&config.channels, &[]);
         }
         Err(e) => {
             println!("✗ Failed to load configuration: {}", e);
@@ -511,8 +760,152 @@ async fn handle_status(config_path: PathBuf) -> AnyhowResult<()> {
     Ok(())
 }
 
+/// Handle connections command: list a channel's active relays from the running service
+async fn handle_connections(config_path: PathBuf, channel: &str) -> AnyhowResult<()> {
+    let body = ipc_request(&config_path, &format!("connections {}", channel))
+        .await
+        .context("Service is not running. Start with: ssh-channels-hub start")?;
+    let response: ConnectionsResponse =
+        toml::from_str(&body).context("Parse connections response")?;
+    let Some(c) = response.connections else {
+        return Err(anyhow::anyhow!(response
+            .error
+            .unwrap_or_else(|| "Empty response from service".to_string())));
+    };
+
+    println!(
+        "Channel '{}': {} active, {} finished, {} sent, {} received",
+        c.channel,
+        c.active.len(),
+        c.relays_total,
+        format_bytes(c.bytes_sent),
+        format_bytes(c.bytes_received)
+    );
+    for relay in &c.active {
+        println!(
+            "  #{:<4} {} -> {} \tup {}s, {} sent, {} received",
+            relay.id,
+            relay.peer,
+            relay.destination,
+            relay.duration_ms / 1000,
+            format_bytes(relay.bytes_sent),
+            format_bytes(relay.bytes_received)
+        );
+    }
+    Ok(())
+}
+
+/// Handle events command: print the running service's events as JSON lines until it stops
+/// or the command is interrupted
+async fn handle_events(config_path: PathBuf) -> AnyhowResult<()> {
+    use std::io::Write;
+
+    let mut stream = connect_ipc(&config_path)
+        .await
+        .context("Service is not running. Start with: ssh-channels-hub start")?;
+    stream.write_all(b"subscribe\n").await?;
+    let mut lines = tokio::io::BufReader::new(stream).lines();
+    let mut stdout = std::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if writeln!(stdout, "{}", line).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Handle logs command: print the tail of the `[logging] file`, then follow it if asked
+async fn handle_logs(
+    config_path: PathBuf,
+    follow: bool,
+    channel: Option<&str>,
+    lines: usize,
+) -> AnyhowResult<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let config = AppConfig::from_file(&config_path).context("Failed to load configuration")?;
+    let Some(file) = config.logging.and_then(|l| l.file) else {
+        return Err(anyhow::anyhow!(
+            "No log file configured: set `file` under [logging] in {}",
+            config_path.display()
+        ));
+    };
+    let path = logging::resolve_path(&file, &run_dir(&config_path))?;
+    let wanted = |line: &str| channel.is_none_or(|c| logging::line_matches_channel(line, c));
+
+    let content = std::fs::read(&path)
+        .with_context(|| format!("Failed to read log file {}", path.display()))?;
+    // Leave a partly written last line to the follow loop
+    let complete = content
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let text = String::from_utf8_lossy(&content[..complete]);
+    let tail: Vec<&str> = text.lines().filter(|line| wanted(line)).collect();
+    // A closed stdout (e.g. piped into `head`) ends the command quietly
+    let mut stdout = std::io::stdout();
+    for line in &tail[tail.len().saturating_sub(lines)..] {
+        if writeln!(stdout, "{}", line).is_err() {
+            return Ok(());
+        }
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut pos = complete as u64;
+    let mut pending = String::new();
+    loop {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        // Briefly missing while the service rotates it
+        let Ok(mut file) = std::fs::File::open(&path) else {
+            continue;
+        };
+        let len = file.metadata()?.len();
+        if len < pos {
+            // Rotated or truncated: the current file starts over
+            pos = 0;
+            pending.clear();
+        }
+        if len == pos {
+            continue;
+        }
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = Vec::new();
+        file.read_to_end(&mut chunk)?;
+        pos += chunk.len() as u64;
+        pending.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = pending.find('\n') {
+            let line: String = pending.drain(..=end).collect();
+            let line = line.trim_end();
+            if wanted(line) && writeln!(stdout, "{}", line).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Byte count with a binary unit, e.g. "1.5 MiB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
 /// Handle validate command
-async fn handle_validate(config_path: Option<std::path::PathBuf>) -> AnyhowResult<()> {
+async fn handle_validate(
+    config_path: Option<std::path::PathBuf>,
+    show_resolved: bool,
+) -> AnyhowResult<()> {
     let path = config_path
         .ok_or_else(|| anyhow::anyhow!("Configuration file path required for validation"))?;
 
@@ -520,16 +913,73 @@ async fn handle_validate(config_path: Option<std::path::PathBuf>) -> AnyhowResul
 
     match AppConfig::from_file(&path) {
         Ok(config) => {
-            println!("✓ Configuration is valid");
+            let diagnostics = validate::validate(&config, &path);
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == validate::Severity::Error)
+                .count();
+            let warnings = diagnostics.len() - errors;
+
+            if errors == 0 {
+                println!("✓ Configuration is valid");
+            } else {
+                println!("✗ Configuration has {} error(s)", errors);
+            }
+            for diagnostic in &diagnostics {
+                println!("  {}", diagnostic);
+            }
+            let unlocated = validate::files_without_locations(&config, &path);
+            if !diagnostics.is_empty() && !unlocated.is_empty() {
+                let files: Vec<String> =
+                    unlocated.iter().map(|p| p.display().to_string()).collect();
+                println!(
+                    "  note: line:column locations are only available for TOML files, not {}",
+                    files.join(", ")
+                );
+            }
+            if warnings > 0 {
+                println!("  {} warning(s)", warnings);
+            }
+            let origin = |path: Option<&PathBuf>| {
+                path.map(|p| format!("  [{}]", p.display()))
+                    .unwrap_or_default()
+            };
             println!("  Hosts configured: {}", config.hosts.len());
             for host in &config.hosts {
-                println!("    - {} ({})", host.name, host.host);
+                println!(
+                    "    - {} ({}){}",
+                    host.name,
+                    host.host,
+                    origin(config.origins.hosts.get(&host.name))
+                );
             }
             println!("  Channels configured: {}", config.channels.len());
             for conn in &config.channels {
+                let from = origin(config.origins.channels.get(&conn.name));
                 let local = conn.ports.local_port.expect("local_port must be set");
+                if conn.channel_type.as_deref() == Some("dynamic") {
+                    println!("    - {} -> socks5 :{}{}", conn.name, local, from);
+                    continue;
+                }
                 let port_info = format!("{}:{}", local, conn.ports.dest_port);
-                println!("    - {} -> {}:{}", conn.name, conn.dest_host, port_info);
+                println!(
+                    "    - {} -> {}:{}{}",
+                    conn.name, conn.dest_host, port_info, from
+                );
+            }
+            if show_resolved {
+                let mut resolved = config.redacted();
+                resolved.vars.clear();
+                resolved.include.clear();
+                let text = toml::to_string_pretty(&resolved)
+                    .context("Failed to serialize resolved configuration")?;
+                println!("\n# Resolved configuration (secrets redacted)\n{}", text);
+            }
+            if errors > 0 {
+                return Err(anyhow::anyhow!(
+                    "Invalid configuration: {} error(s) found",
+                    errors
+                ));
             }
             Ok(())
         }
@@ -540,16 +990,109 @@ async fn handle_validate(config_path: Option<std::path::PathBuf>) -> AnyhowResul
     }
 }
 
+/// Handle migrate command
+fn handle_migrate(path: PathBuf, dry_run: bool) -> AnyhowResult<()> {
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let format = ConfigFormat::from_path(&path);
+    let mut doc: toml_edit::DocumentMut = config_format::to_toml_text(&content, format)?
+        .parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let report = config_migrate::migrate(&mut doc)?;
+    if report.changes.is_empty() {
+        println!(
+            "✓ {} is already at config version {}",
+            path.display(),
+            report.to_version
+        );
+        return Ok(());
+    }
+
+    // Make sure the result loads before touching the file
+    AppConfig::from_document(doc.clone(), None).context("Migrated configuration does not load")?;
+
+    let migrated = match format {
+        ConfigFormat::Toml => doc.to_string(),
+        other => config_format::convert(&doc.to_string(), ConfigFormat::Toml, other)?,
+    };
+
+    println!(
+        "Config version {} -> {} ({}):",
+        report.from_version,
+        report.to_version,
+        path.display()
+    );
+    for change in &report.changes {
+        println!("  - {}", change);
+    }
+
+    if dry_run {
+        println!("\n{}", migrated);
+        return Ok(());
+    }
+
+    let backup = backup_path(&path);
+    std::fs::copy(&path, &backup)
+        .with_context(|| format!("Failed to write backup {}", backup.display()))?;
+    std::fs::write(&path, migrated)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    println!("✓ Configuration migrated");
+    println!("  Backup: {}", backup.display());
+    Ok(())
+}
+
+/// First unused `<file>.bak`, `<file>.bak.1`, ... next to `path`
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let base = PathBuf::from(backup);
+    let mut candidate = base.clone();
+    let mut n = 1;
+    while candidate.exists() {
+        let mut numbered = base.as_os_str().to_owned();
+        numbered.push(format!(".{}", n));
+        candidate = PathBuf::from(numbered);
+        n += 1;
+    }
+    candidate
+}
+
+/// Handle schema command
+fn handle_schema(output: Option<PathBuf>) -> AnyhowResult<()> {
+    let schema = serde_json::to_string_pretty(&AppConfig::json_schema())
+        .context("Failed to serialize JSON Schema")?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, format!("{}\n", schema))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("✓ JSON Schema written to: {}", path.display());
+        }
+        None => println!("{}", schema),
+    }
+    Ok(())
+}
+
 /// Handle generate command
 async fn handle_generate(
     ssh_config: Option<std::path::PathBuf>,
     output: Option<std::path::PathBuf>,
+    hosts: Option<String>,
+    merge: bool,
+    format: Option<ConfigFormat>,
 ) -> AnyhowResult<()> {
     let ssh_config_path = ssh_config.unwrap_or_else(default_ssh_config_path);
 
     info!("Reading SSH config from: {}", ssh_config_path.display());
 
-    let entries = parse_ssh_config(&ssh_config_path).context("Failed to parse SSH config file")?;
+    let mut entries =
+        parse_ssh_config(&ssh_config_path).context("Failed to parse SSH config file")?;
+
+    if let Some(patterns) = &hosts {
+        entries.retain(|entry| alias_matches(&entry.host, patterns));
+    }
 
     if entries.is_empty() {
         println!("⚠ No valid SSH config entries found");
@@ -560,16 +1103,29 @@ async fn handle_generate(
 
     let app_config = AppConfig::from_ssh_config_entries(entries);
 
+    let format = format
+        .or_else(|| output.as_deref().and_then(ConfigFormat::from_extension))
+        .unwrap_or(ConfigFormat::Toml);
     let output_path = output.unwrap_or_else(|| {
         std::env::current_dir()
             .unwrap_or_else(|_| std::path::PathBuf::from("."))
-            .join("configs.toml")
+            .join(format!("configs.{}", format.extension()))
     });
 
+    if merge && output_path.exists() {
+        if format != ConfigFormat::Toml {
+            return Err(anyhow::anyhow!(
+                "--merge only supports TOML output (got {})",
+                format
+            ));
+        }
+        return merge_into_existing(&output_path, &app_config);
+    }
+
     info!("Generating configuration to: {}", output_path.display());
 
     app_config
-        .to_file(&output_path)
+        .to_file(&output_path, format)
         .context("Failed to write configuration file")?;
 
     println!("✓ Configuration generated successfully");
@@ -578,6 +1134,10 @@ async fn handle_generate(
     for host in &app_config.hosts {
         println!("    - {} ({})", host.name, host.host);
     }
+    println!("  Channels generated: {}", app_config.channels.len());
+    for conn in &app_config.channels {
+        println!("    - {} (host: {})", conn.name, conn.hostname);
+    }
 
     // Warn about password placeholders
     let password_hosts: Vec<_> = app_config
@@ -594,98 +1154,341 @@ async fn handle_generate(
         println!("  Please update the password in the generated config file.");
     }
 
-    println!(
-        "\n💡 Note: You need to manually add [[channels]] sections to define port forwarding."
-    );
+    if app_config.channels.is_empty() {
+        println!(
+            "\n💡 Note: No LocalForward/RemoteForward/DynamicForward found; add [[channels]] sections to define port forwarding."
+        );
+    }
+
+    Ok(())
+}
+
+/// Merge generated hosts/channels into an existing config file and print what changed
+fn merge_into_existing(output_path: &Path, generated: &AppConfig) -> AnyhowResult<()> {
+    info!("Merging configuration into: {}", output_path.display());
 
+    let existing =
+        std::fs::read_to_string(output_path).context("Failed to read existing config file")?;
+    let outcome = config_merge::merge_generated(&existing, generated)
+        .context("Failed to merge configuration")?;
+
+    if outcome.changes.is_empty() {
+        println!("✓ Configuration already up to date");
+        println!("  Output file: {}", output_path.display());
+        return Ok(());
+    }
+
+    std::fs::write(output_path, &outcome.content).context("Failed to write configuration file")?;
+
+    println!("✓ Configuration merged successfully");
+    println!("  Output file: {}", output_path.display());
+    println!("  Changes: {}", outcome.changes.len());
+    for change in &outcome.changes {
+        println!("    {}", change);
+    }
+    Ok(())
+}
+
+/// Handle convert command
+fn handle_convert(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    format: Option<ConfigFormat>,
+) -> AnyhowResult<()> {
+    let from = ConfigFormat::from_path(&input);
+    let to = format
+        .or_else(|| output.as_deref().and_then(ConfigFormat::from_extension))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Target format required: pass --format or an --output path ending in .toml, .yaml or .json"
+            )
+        })?;
+
+    let content = std::fs::read_to_string(&input)
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let converted = config_format::convert(&content, from, to)
+        .with_context(|| format!("Failed to convert {}", input.display()))?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, converted)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "✓ Converted {} ({}) -> {} ({})",
+                input.display(),
+                from,
+                path.display(),
+                to
+            );
+            println!("  Note: comments are not carried over");
+        }
+        None => print!("{}", converted),
+    }
     Ok(())
 }
 
-/// Handle test command - verify channels are working
-async fn handle_test(config_path: std::path::PathBuf) -> AnyhowResult<()> {
+/// Result of testing one channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum TestOutcome {
+    Ok,
+    /// Connected according to the service, but nothing got through
+    Failed,
+    /// The service has no SSH session for the channel
+    Down,
+    NotRunning,
+}
+
+impl TestOutcome {
+    /// Process exit code; `test` exits with the highest one among the tested channels
+    fn exit_code(self) -> i32 {
+        match self {
+            TestOutcome::Ok => 0,
+            TestOutcome::Failed => 2,
+            TestOutcome::Down => 3,
+            TestOutcome::NotRunning => 4,
+        }
+    }
+}
+
+/// One channel in the `test` report
+#[derive(Debug, serde::Serialize)]
+struct TestResult {
+    channel: String,
+    #[serde(rename = "type")]
+    channel_type: String,
+    /// Live state reported by the service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    /// What was connected to: the local listener, or the server's port for remote forwards
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    outcome: TestOutcome,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// Upper bound for testing one channel (a remote forward needs a whole SSH login)
+const CHANNEL_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Handle test command: check every channel of the running service end to end.
+/// Returns the exit code (see [`TestOutcome::exit_code`]).
+async fn handle_test(config_path: PathBuf, only: Option<&str>, json: bool) -> AnyhowResult<i32> {
     info!("Loading configuration from: {}", config_path.display());
 
     let config = AppConfig::from_file(&config_path).context("Failed to load configuration")?;
-
-    if config.channels.is_empty() {
+    let mut channels = config
+        .build_channels()
+        .context("Failed to resolve channels")?;
+    if let Some(name) = only {
+        channels.retain(|c| c.name == name);
+        if channels.is_empty() {
+            anyhow::bail!("No channel named '{}' in {}", name, config_path.display());
+        }
+    }
+    if channels.is_empty() {
         println!("No channels configured");
-        return Ok(());
+        return Ok(0);
     }
 
-    println!("Testing {} channel(s)...\n", config.channels.len());
+    let live = query_status_via_ipc(&config_path).await.ok();
+    if !json {
+        match &live {
+            Some(_) => println!("Testing {} channel(s)...\n", channels.len()),
+            None => println!("Service is not running\n"),
+        }
+    }
 
-    let mut all_passed = true;
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, channel) in channels.into_iter().enumerate() {
+        let status = live
+            .as_ref()
+            .and_then(|s| s.channels.iter().find(|c| c.name == channel.name).cloned());
+        let running = live.is_some();
+        tasks.spawn(async move { (index, test_channel(channel, running, status).await) });
+    }
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.context("Channel test panicked")?);
+    }
+    results.sort_by_key(|(index, _)| *index);
+    let results: Vec<TestResult> = results.into_iter().map(|(_, r)| r).collect();
+    let code = results.iter().map(|r| r.exit_code).max().unwrap_or(0);
 
-    for conn in &config.channels {
-        let is_remote = conn
-            .channel_type
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "exit_code": code,
+                "channels": results,
+            }))?
+        );
+        return Ok(code);
+    }
+
+    for r in &results {
+        let mark = if r.outcome == TestOutcome::Ok {
+            "✓"
+        } else {
+            "✗"
+        };
+        let target = r
+            .target
             .as_deref()
-            .map(|t| t == "forwarded-tcpip")
-            .unwrap_or(false);
+            .map(|t| format!(" {}", t))
+            .unwrap_or_default();
+        let state = r
+            .state
+            .as_deref()
+            .map(|s| format!(" [{}]", s))
+            .unwrap_or_default();
+        let detail = r.detail.as_deref().unwrap_or_default();
+        println!(
+            "{} {} ({}{}){}: {}",
+            mark, r.channel, r.channel_type, target, state, detail
+        );
+    }
+    println!();
 
-        if is_remote {
-            print!("Channel '{}' (remote forward)... ", conn.name);
+    match code {
+        0 => println!("✓ All channels are working correctly!"),
+        _ if live.is_none() => println!(
+            "✗ Start the service first: ssh-channels-hub start -c {}",
+            config_path.display()
+        ),
+        _ => {
+            println!("✗ Some channels failed the connection test");
+            println!("\nTroubleshooting tips:");
+            println!("1. `ssh-channels-hub status` shows each channel's last error");
             println!(
-                "skipped (test connects to local listener; use remote port on server to verify)"
+                "2. `ssh-channels-hub logs --channel <name>` shows what the service logged for it"
             );
-            continue;
+            println!("3. Ensure the destination is reachable from the SSH server");
         }
+    }
+    Ok(code)
+}
 
-        let local_port = conn.ports.local_port.expect("local_port must be set");
-        let dest_port = conn.ports.dest_port;
-        let dest_host = &conn.dest_host;
+/// Test one channel, given whether the service is running and its live status of the channel
+async fn test_channel(
+    channel: config::ChannelConfig,
+    running: bool,
+    status: Option<ssh::ChannelStatus>,
+) -> TestResult {
+    let mut result = TestResult {
+        channel: channel.name.clone(),
+        channel_type: channel.channel_type.clone(),
+        state: status.as_ref().map(|s| s.state.to_string()),
+        target: None,
+        outcome: TestOutcome::Ok,
+        exit_code: 0,
+        detail: None,
+    };
+    let finish = |mut result: TestResult, outcome: TestOutcome, detail: String| {
+        result.outcome = outcome;
+        result.exit_code = outcome.exit_code();
+        result.detail = Some(detail);
+        result
+    };
 
-        print!(
-            "Testing channel '{}' (local:{} -> {}:{})... ",
-            conn.name, local_port, dest_host, dest_port
-        );
+    let status = match status {
+        _ if !running => {
+            return finish(
+                result,
+                TestOutcome::NotRunning,
+                "service is not running".to_string(),
+            )
+        }
+        None => {
+            return finish(
+                result,
+                TestOutcome::NotRunning,
+                "not known to the running service (restart it to load new channels)".to_string(),
+            )
+        }
+        Some(status) => status,
+    };
+    if status.state != ssh::ChannelState::Connected {
+        let detail = match &status.reason {
+            Some(reason) => format!("no SSH session: {}", reason),
+            None => "no SSH session".to_string(),
+        };
+        return finish(result, TestOutcome::Down, detail);
+    }
 
-        // First check if port is listening
-        match test_port_connection("127.0.0.1", local_port).await {
-            Ok(false) => {
-                println!("✗ Port not listening");
-                all_passed = false;
-                continue;
-            }
-            Err(e) => {
-                println!("✗ Error checking port: {}", e);
-                all_passed = false;
-                continue;
-            }
-            Ok(true) => {
-                // Port is listening, now test if tunnel is actually working
-                match test_tunnel_connection("127.0.0.1", local_port).await {
-                    Ok(true) => {
-                        println!("✓ Tunnel working");
-                    }
-                    Ok(false) => {
-                        println!("✗ Tunnel dead (SSH connection may be broken)");
-                        all_passed = false;
-                    }
-                    Err(e) => {
-                        println!("✗ Error testing tunnel: {}", e);
-                        all_passed = false;
-                    }
-                }
-            }
+    let check = match &channel.params {
+        config::ChannelTypeParams::DirectTcpIp {
+            listen_host,
+            local_port,
+            ..
         }
+        | config::ChannelTypeParams::Dynamic {
+            listen_host,
+            local_port,
+        } => {
+            let host = connect_host(listen_host);
+            result.target = Some(format_addr(host, *local_port));
+            let check = if channel.channel_type == "dynamic" {
+                tokio::time::timeout(
+                    CHANNEL_TEST_TIMEOUT,
+                    port_check::check_socks_listener(host, *local_port),
+                )
+                .await
+            } else {
+                tokio::time::timeout(
+                    CHANNEL_TEST_TIMEOUT,
+                    port_check::check_tunnel(host, *local_port),
+                )
+                .await
+            };
+            check.map(|r| r.map(|_| "tunnel working".to_string()))
+        }
+        config::ChannelTypeParams::ForwardedTcpIp {
+            remote_bind_port, ..
+        } => {
+            // Go through the host the service is connected to
+            let endpoint = channel
+                .hosts
+                .iter()
+                .find(|h| status.active_host.as_deref() == Some(h.name.as_str()))
+                .unwrap_or(&channel.hosts[0]);
+            result.target = Some(format!("{}:{}", endpoint.name, remote_bind_port));
+            tokio::time::timeout(
+                CHANNEL_TEST_TIMEOUT,
+                ssh::check_remote_forward(&channel, endpoint),
+            )
+            .await
+            .map(|r| r.map(|_| "round trip through the server working".to_string()))
+        }
+        config::ChannelTypeParams::Session { .. } => {
+            Ok(Ok("session connected (no tunnel to test)".to_string()))
+        }
+    };
+    match check {
+        Ok(Ok(detail)) => finish(result, TestOutcome::Ok, detail),
+        Ok(Err(e)) => finish(result, TestOutcome::Failed, e),
+        Err(_) => finish(
+            result,
+            TestOutcome::Failed,
+            format!("no result within {}s", CHANNEL_TEST_TIMEOUT.as_secs()),
+        ),
     }
+}
 
-    println!();
+/// Address to reach a listener bound on `listen_host` (wildcards become loopback)
+fn connect_host(listen_host: &str) -> &str {
+    match listen_host {
+        "0.0.0.0" => "127.0.0.1",
+        "::" | "[::]" => "::1",
+        host => host,
+    }
+}
 
-    if all_passed {
-        println!("✓ All channels are working correctly!");
-        Ok(())
+fn format_addr(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
     } else {
-        println!("✗ Some channels failed the connection test");
-        println!("\nTroubleshooting tips:");
-        println!(
-            "1. Make sure the service is running: cargo run start -c {}",
-            config_path.display()
-        );
-        println!("2. Check if ports are listening: netstat -an | grep LISTEN");
-        println!("3. Verify SSH connection is established (check logs with --debug)");
-        println!("4. Ensure remote service is accessible from the SSH server");
-        Err(anyhow::anyhow!("Some channels failed the connection test"))
+        format!("{}:{}", host, port)
     }
 }
@@ -0,0 +1,499 @@
+use crate::service::ServiceManager;
+use crate::ssh::{ChannelState, ChannelStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+const PREFIX: &str = "ssh_channels_hub";
+
+/// Upper bounds (seconds) of the connect latency histogram buckets
+const LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Largest request head accepted by the metrics endpoint
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Counters of one channel, updated by its `SshManager` and exported on `/metrics`
+#[derive(Debug, Default)]
+pub struct ChannelMetrics {
+    /// Connection attempts after the first one (retries, failovers, failbacks)
+    pub reconnects: AtomicU64,
+    /// Relays that have ended
+    pub relays_total: AtomicU64,
+    /// Bytes from local clients (or the local service, for remote forwards) into the tunnel
+    pub bytes_sent: AtomicU64,
+    /// Bytes out of the tunnel to local clients (or the local service)
+    pub bytes_received: AtomicU64,
+    /// Direct-tcpip channels the server refused or that failed to open
+    pub channel_open_failures: AtomicU64,
+    /// Logins rejected by the server
+    pub auth_failures: AtomicU64,
+    /// Time from TCP connect to authenticated session
+    pub connect_latency: Histogram,
+    /// Relays in progress, by id (ids grow, so oldest first)
+    active: Mutex<BTreeMap<u64, Arc<Relay>>>,
+    next_relay_id: AtomicU64,
+}
+
+impl ChannelMetrics {
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Register a relay from `peer` to `destination`; it is listed as active until the
+    /// returned guard is finished or dropped
+    pub fn relay(self: &Arc<Self>, peer: String, destination: String) -> ActiveRelay {
+        let relay = Arc::new(Relay {
+            id: self.next_relay_id.fetch_add(1, Ordering::Relaxed) + 1,
+            peer,
+            destination,
+            started_at: SystemTime::now(),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+        });
+        self.active_list().insert(relay.id, Arc::clone(&relay));
+        ActiveRelay {
+            channel: Arc::clone(self),
+            relay,
+        }
+    }
+
+    pub fn active_relay_count(&self) -> usize {
+        self.active_list().len()
+    }
+
+    /// Per-channel totals and the relays in progress
+    pub fn connections(&self, channel: &str) -> ChannelConnections {
+        ChannelConnections {
+            channel: channel.to_string(),
+            relays_total: self.relays_total.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            active: self
+                .active_list()
+                .values()
+                .map(|relay| relay.record(None))
+                .collect(),
+        }
+    }
+
+    fn active_list(&self) -> MutexGuard<'_, BTreeMap<u64, Arc<Relay>>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A relayed connection; its byte counts grow as data flows
+#[derive(Debug)]
+struct Relay {
+    id: u64,
+    peer: String,
+    destination: String,
+    started_at: SystemTime,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Relay {
+    fn record(&self, close_reason: Option<String>) -> RelayRecord {
+        RelayRecord {
+            id: self.id,
+            peer: self.peer.clone(),
+            destination: self.destination.clone(),
+            started_at: unix_secs(self.started_at),
+            ended_at: close_reason.as_ref().map(|_| unix_secs(SystemTime::now())),
+            duration_ms: self.started_at.elapsed().unwrap_or_default().as_millis() as u64,
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            close_reason,
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// One relay as listed by `connections`, or as it ended
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayRecord {
+    pub id: u64,
+    /// Local client address (for remote forwards: the originator the server reported)
+    pub peer: String,
+    pub destination: String,
+    /// Unix time (seconds)
+    pub started_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<u64>,
+    /// Time since start, or total duration once ended
+    pub duration_ms: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// "closed" when both sides finished normally, else the I/O error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_reason: Option<String>,
+}
+
+/// Reply to the `connections <channel>` IPC request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelConnections {
+    pub channel: String,
+    pub relays_total: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    #[serde(default)]
+    pub active: Vec<RelayRecord>,
+}
+
+/// Registration of a relay in progress; ends it when finished or dropped
+pub struct ActiveRelay {
+    channel: Arc<ChannelMetrics>,
+    relay: Arc<Relay>,
+}
+
+impl ActiveRelay {
+    /// Wrap the local end of the relay so its bytes are counted as they flow, which keeps
+    /// long-lived relays visible before they end
+    pub fn meter<S>(&self, inner: S) -> Metered<'_, S> {
+        Metered {
+            inner,
+            channel: &self.channel,
+            relay: &self.relay,
+        }
+    }
+
+    /// The relay as it stands
+    pub fn record(&self) -> RelayRecord {
+        self.relay.record(None)
+    }
+
+    /// End the relay with the result of its copy loop
+    pub fn finish(self, result: &io::Result<(u64, u64)>) -> RelayRecord {
+        let reason = match result {
+            Ok(_) => "closed".to_string(),
+            Err(e) => e.to_string(),
+        };
+        self.relay.record(Some(reason))
+    }
+}
+
+impl Drop for ActiveRelay {
+    fn drop(&mut self) {
+        self.channel.active_list().remove(&self.relay.id);
+        ChannelMetrics::inc(&self.channel.relays_total);
+    }
+}
+
+/// Cumulative histogram over [`LATENCY_BUCKETS`]
+#[derive(Debug, Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, value: Duration) {
+        let secs = value.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if secs <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(value.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Local end of a relay, counting bytes for the relay and its channel
+pub struct Metered<'a, S> {
+    inner: S,
+    channel: &'a ChannelMetrics,
+    relay: &'a Relay,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Metered<'_, S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        self.channel.bytes_sent.fetch_add(read, Ordering::Relaxed);
+        self.relay.bytes_sent.fetch_add(read, Ordering::Relaxed);
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Metered<'_, S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            let written = written as u64;
+            self.channel
+                .bytes_received
+                .fetch_add(written, Ordering::Relaxed);
+            self.relay
+                .bytes_received
+                .fetch_add(written, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Prometheus text exposition of every channel's status and counters
+pub fn render(channels: &[(ChannelStatus, Arc<ChannelMetrics>)]) -> String {
+    let mut out = String::new();
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+
+    let mut family = |name: &str,
+                      kind: &str,
+                      help: &str,
+                      value: &dyn Fn(&ChannelStatus, &ChannelMetrics) -> f64| {
+        let _ = writeln!(out, "# HELP {PREFIX}_{name} {help}");
+        let _ = writeln!(out, "# TYPE {PREFIX}_{name} {kind}");
+        for (status, metrics) in channels {
+            let _ = writeln!(
+                out,
+                "{PREFIX}_{name}{{channel=\"{}\"}} {}",
+                escape_label(&status.name),
+                value(status, metrics)
+            );
+        }
+    };
+
+    family(
+        "channel_connected",
+        "gauge",
+        "Whether the channel has a live SSH session (1) or not (0)",
+        &|s, _| f64::from(u8::from(s.state == ChannelState::Connected)),
+    );
+    family(
+        "channel_sessions_up",
+        "gauge",
+        "SSH sessions of the channel that are connected",
+        &|s, _| s.sessions_up as f64,
+    );
+    family(
+        "channel_failures",
+        "gauge",
+        "Consecutive failed connection attempts since the last healthy connection",
+        &|s, _| f64::from(s.failures),
+    );
+    family(
+        "reconnects_total",
+        "counter",
+        "Connection attempts after the first one",
+        &|_, m| load(&m.reconnects),
+    );
+    family(
+        "active_relays",
+        "gauge",
+        "Connections currently relayed through the channel",
+        &|_, m| m.active_relay_count() as f64,
+    );
+    family(
+        "relays_total",
+        "counter",
+        "Relays that have ended",
+        &|_, m| load(&m.relays_total),
+    );
+    family(
+        "bytes_sent_total",
+        "counter",
+        "Bytes sent into the tunnel from the local side",
+        &|_, m| load(&m.bytes_sent),
+    );
+    family(
+        "bytes_received_total",
+        "counter",
+        "Bytes received from the tunnel to the local side",
+        &|_, m| load(&m.bytes_received),
+    );
+    family(
+        "channel_open_failures_total",
+        "counter",
+        "Direct-tcpip channels that could not be opened",
+        &|_, m| load(&m.channel_open_failures),
+    );
+    family(
+        "auth_failures_total",
+        "counter",
+        "Logins rejected by the SSH server",
+        &|_, m| load(&m.auth_failures),
+    );
+
+    let name = format!("{PREFIX}_connect_duration_seconds");
+    let _ = writeln!(
+        out,
+        "# HELP {name} Time from TCP connect to authenticated SSH session"
+    );
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    for (status, metrics) in channels {
+        let channel = escape_label(&status.name);
+        let histogram = &metrics.connect_latency;
+        for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{channel=\"{channel}\",le=\"{bound}\"}} {}",
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = histogram.count.load(Ordering::Relaxed);
+        let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{channel=\"{channel}\",le=\"+Inf\"}} {count}"
+        );
+        let _ = writeln!(out, "{name}_sum{{channel=\"{channel}\"}} {sum}");
+        let _ = writeln!(out, "{name}_count{{channel=\"{channel}\"}} {count}");
+    }
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answer `GET /metrics` on `listener` until cancelled
+pub async fn serve(listener: TcpListener, service: Arc<ServiceManager>, cancel: CancellationToken) {
+    let addr = listener
+        .local_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_default();
+    info!(listen = %addr, "Metrics endpoint started");
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            accept_result = listener.accept() => {
+                let Ok((stream, _)) = accept_result else {
+                    continue;
+                };
+                let service = Arc::clone(&service);
+                tokio::spawn(async move {
+                    if let Err(e) = handle_request(stream, &service).await {
+                        debug!(error = ?e, "Metrics request failed");
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn handle_request(mut stream: TcpStream, service: &ServiceManager) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || head.len() + n > MAX_REQUEST_HEAD {
+            return Ok(());
+        }
+        head.extend_from_slice(&chunk[..n]);
+    }
+
+    let request_line = String::from_utf8_lossy(&head);
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", service.metrics().await),
+        (Some("GET"), _) => ("404 Not Found", "Not found; try /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_exposition() {
+        let metrics = Arc::new(ChannelMetrics::default());
+        ChannelMetrics::inc(&metrics.reconnects);
+        metrics.connect_latency.observe(Duration::from_millis(300));
+        let _relay = metrics.relay("127.0.0.1:50000".to_string(), "db:5432".to_string());
+
+        let mut status = ChannelStatus::new("db \"main\"");
+        status.state = ChannelState::Connected;
+        let text = render(&[(status, metrics)]);
+
+        assert!(text.contains("# TYPE ssh_channels_hub_reconnects_total counter"));
+        assert!(text.contains("ssh_channels_hub_channel_connected{channel=\"db \\\"main\\\"\"} 1"));
+        assert!(text.contains("ssh_channels_hub_reconnects_total{channel=\"db \\\"main\\\"\"} 1"));
+        assert!(text.contains("ssh_channels_hub_active_relays{channel=\"db \\\"main\\\"\"} 1"));
+        assert!(text.contains(
+            "ssh_channels_hub_connect_duration_seconds_bucket{channel=\"db \\\"main\\\"\",le=\"0.25\"} 0"
+        ));
+        assert!(text.contains(
+            "ssh_channels_hub_connect_duration_seconds_bucket{channel=\"db \\\"main\\\"\",le=\"0.5\"} 1"
+        ));
+        assert!(text.contains(
+            "ssh_channels_hub_connect_duration_seconds_count{channel=\"db \\\"main\\\"\"} 1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_relay_accounting() {
+        let metrics = Arc::new(ChannelMetrics::default());
+        let relay = metrics.relay("127.0.0.1:50000".to_string(), "db:5432".to_string());
+
+        // Reading from the local end is traffic into the tunnel, writing to it comes back out
+        let (local, mut client) = tokio::io::duplex(64);
+        let mut local = relay.meter(local);
+        client.write_all(b"select 1").await.unwrap();
+        let mut buf = [0u8; 8];
+        local.read_exact(&mut buf).await.unwrap();
+        local.write_all(b"1").await.unwrap();
+        drop(local);
+
+        let listed = metrics.connections("db");
+        assert_eq!(listed.active.len(), 1);
+        assert_eq!(listed.active[0].peer, "127.0.0.1:50000");
+        assert_eq!(
+            (listed.active[0].bytes_sent, listed.active[0].bytes_received),
+            (8, 1)
+        );
+        assert_eq!(listed.active[0].close_reason, None);
+
+        let record = relay.finish(&Ok((8, 1)));
+        assert_eq!(record.close_reason.as_deref(), Some("closed"));
+        assert!(record.ended_at.is_some());
+
+        let totals = metrics.connections("db");
+        assert!(totals.active.is_empty());
+        assert_eq!(totals.relays_total, 1);
+        assert_eq!((totals.bytes_sent, totals.bytes_received), (8, 1));
+    }
+}
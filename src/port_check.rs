@@ -1,6 +1,7 @@
 use crate::error::{AppError, Result};
 use std::net::TcpListener;
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpSocket, TcpStream};
 use tokio::time::timeout;
 
@@ -71,70 +72,79 @@ pub async fn check_ports(ports: &[u16]) -> Result<Vec<u16>> {
     Ok(occupied)
 }
 
-/// Test if a TCP connection can be established to a port
-/// This is useful for verifying that a port forwarding channel is actually working
-pub async fn test_port_connection(host: &str, port: u16) -> Result<bool> {
-    let addr = format!("{}:{}", host, port);
+/// How long a connection through a tunnel must stay open (or deliver data) to count as working
+const TUNNEL_SETTLE: Duration = Duration::from_secs(2);
 
-    // Try to connect with a timeout
-    match timeout(Duration::from_secs(2), TcpStream::connect(&addr)).await {
-        Ok(Ok(_)) => Ok(true),
-        Ok(Err(_)) => Ok(false),
-        Err(_) => Ok(false), // Timeout
-    }
+/// Connect to a local tunnel listener at `host:port` and check that the connection is relayed.
+/// The service closes a client as soon as it cannot relay it (no SSH session, or the server
+/// refused the destination), so a connection that stays open or delivers data counts as working.
+///
+/// Silence is not proof: a destination the server is still connecting to after
+/// [`TUNNEL_SETTLE`], or one that accepts and then never answers, also passes. Only a
+/// `health_check` probe confirms the service behind the tunnel.
+pub async fn check_tunnel(host: &str, port: u16) -> std::result::Result<(), String> {
+    let mut stream = connect(host, port).await?;
+    stream_stays_open(&mut stream, TUNNEL_SETTLE).await
 }
 
-/// Test if an SSH tunnel is actually working by attempting to send/receive data
-/// This detects cases where the local port is listening but the SSH connection is dead
-pub async fn test_tunnel_connection(host: &str, port: u16) -> Result<bool> {
-    use tokio::io::AsyncWriteExt;
-
-    let addr = format!("{}:{}", host, port);
-
-    // Try to connect with a timeout
-    let mut stream = match timeout(Duration::from_secs(2), TcpStream::connect(&addr)).await {
-        Ok(Ok(s)) => s,
-        Ok(Err(_)) => return Ok(false),
-        Err(_) => return Ok(false), // Timeout
+/// Connect to a local SOCKS5 listener at `host:port` and check that it answers the greeting
+pub async fn check_socks_listener(host: &str, port: u16) -> std::result::Result<(), String> {
+    let mut stream = connect(host, port).await?;
+    let mut reply = [0u8; 2];
+    let handshake = async {
+        stream.write_all(&[0x05, 0x01, 0x00]).await?;
+        stream.read_exact(&mut reply).await
     };
-
-    // Try to send a small amount of data to verify the tunnel is working
-    // If the SSH connection is dead, this will fail with connection reset
-    match timeout(Duration::from_secs(1), stream.write_all(b"X")).await {
-        Ok(Ok(_)) => {
-            // Successfully sent data, tunnel appears to be working
-            Ok(true)
-        }
-        Ok(Err(e)) => {
-            // Check if it's a connection reset error (SSH tunnel is dead)
-            if e.kind() == std::io::ErrorKind::ConnectionReset
-                || e.kind() == std::io::ErrorKind::BrokenPipe
-            {
-                Ok(false)
-            } else {
-                // Other error, but connection was established, so consider it working
-                Ok(true)
-            }
-        }
-        Err(_) => {
-            // Timeout on write, but connection was established
-            // This might happen if the remote service doesn't respond, but tunnel is working
-            Ok(true)
-        }
+    match timeout(TUNNEL_SETTLE, handshake).await {
+        Ok(Ok(_)) if reply == [0x05, 0x00] => Ok(()),
+        Ok(Ok(_)) => Err(format!("unexpected SOCKS5 reply {:02x?}", reply)),
+        Ok(Err(e)) => Err(format!("SOCKS5 handshake failed: {}", e)),
+        Err(_) => Err("no SOCKS5 reply".to_string()),
     }
 }
 
-// /// Test multiple port connections and return results
-// pub async fn test_port_connections(connections: &[(String, u16)]) -> Vec<(String, u16, bool)> {
-//     let mut results = Vec::new();
+/// Wait up to `wait` on a freshly opened tunnel connection: data or silence means it is
+/// relayed, an immediate close or reset means the other end gave up on it
+pub async fn stream_stays_open<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    wait: Duration,
+) -> std::result::Result<(), String> {
+    let mut buf = [0u8; 1];
+    match timeout(wait, stream.read(&mut buf)).await {
+        Err(_) | Ok(Ok(1..)) => Ok(()),
+        Ok(Ok(_)) => Err(
+            "closed without data (SSH session down, or the destination refused the connection)"
+                .to_string(),
+        ),
+        Ok(Err(e)) => Err(format!("connection broken: {}", e)),
+    }
+}
 
-//     for (host, port) in connections {
-//         let connected = test_port_connection(host, *port).await.unwrap_or(false);
-//         results.push((host.clone(), *port, connected));
-//     }
+/// Wait up to `wait` for the first byte on a freshly opened tunnel connection; unlike
+/// [`stream_stays_open`], silence counts as a failure
+pub async fn stream_answers<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    wait: Duration,
+) -> std::result::Result<(), String> {
+    let mut buf = [0u8; 1];
+    match timeout(wait, stream.read(&mut buf)).await {
+        Ok(Ok(1..)) => Ok(()),
+        Ok(Ok(_)) => Err("closed without data (the local target refused the connection)".to_string()),
+        Ok(Err(e)) => Err(format!("connection broken: {}", e)),
+        Err(_) => Err(format!(
+            "no data within {}s (a service that waits for the client to speak first needs a health_check)",
+            wait.as_secs()
+        )),
+    }
+}
 
-//     results
-// }
+async fn connect(host: &str, port: u16) -> std::result::Result<TcpStream, String> {
+    match timeout(Duration::from_secs(2), TcpStream::connect((host, port))).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) => Err(format!("not listening: {}", e)),
+        Err(_) => Err("not listening: connect timed out".to_string()),
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -153,4 +163,30 @@ mod tests {
         let available = is_port_available(port).await;
         assert!(available.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_check_tunnel_detects_closed_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            // First client is dropped at once, like a relay whose channel open failed
+            let (first, _) = listener.accept().await.unwrap();
+            drop(first);
+            let (_second, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let mut first = connect("127.0.0.1", port).await.unwrap();
+        assert!(stream_stays_open(&mut first, Duration::from_secs(2))
+            .await
+            .is_err());
+        let mut second = connect("127.0.0.1", port).await.unwrap();
+        assert!(stream_stays_open(&mut second, Duration::from_millis(200))
+            .await
+            .is_ok());
+        // Open but silent is not an answer
+        assert!(stream_answers(&mut second, Duration::from_millis(200))
+            .await
+            .is_err());
+    }
 }
@@ -0,0 +1,162 @@
+use crate::config::{BackoffStrategy, ReconnectionConfig};
+use std::time::Duration;
+
+/// Reconnection schedule of one channel, built from its effective `ReconnectionConfig`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub strategy: BackoffStrategy,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Retries allowed after a failure before giving up (0 = unlimited)
+    pub max_retries: u32,
+    /// Add a random `[0, initial_delay)` to fixed and exponential delays
+    pub jitter: bool,
+    /// A connection that stayed up this long counts as healthy and resets the schedule
+    pub reset_after: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &ReconnectionConfig) -> Self {
+        let initial_delay = Duration::from_secs(config.initial_delay_secs);
+        Self {
+            strategy: config.strategy(),
+            initial_delay,
+            max_delay: Duration::from_secs(config.max_delay_secs).max(initial_delay),
+            max_retries: config.max_retries,
+            jitter: config.jitter,
+            reset_after: Duration::from_secs(config.reset_after_secs),
+        }
+    }
+
+    /// Fresh attempt state following this policy
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            policy: self.clone(),
+            failures: 0,
+            previous: self.initial_delay,
+            rng: fastrand::Rng::new(),
+        }
+    }
+}
+
+/// Consecutive-failure count and delay state for one channel
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    policy: RetryPolicy,
+    failures: u32,
+    previous: Duration,
+    rng: fastrand::Rng,
+}
+
+impl Backoff {
+    /// Record a failed attempt and return how long to wait before the next one,
+    /// or `None` once `max_retries` retries have been used up
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        let policy = self.policy.clone();
+        if policy.max_retries > 0 && self.failures >= policy.max_retries {
+            return None;
+        }
+        self.failures += 1;
+
+        let delay = match policy.strategy {
+            BackoffStrategy::Fixed => policy.initial_delay,
+            BackoffStrategy::Exponential => {
+                let factor = 2u32.saturating_pow(self.failures - 1);
+                policy
+                    .initial_delay
+                    .saturating_mul(factor)
+                    .min(policy.max_delay)
+            }
+            BackoffStrategy::DecorrelatedJitter => {
+                let upper = self.previous.saturating_mul(3).min(policy.max_delay);
+                self.random_between(policy.initial_delay, upper)
+            }
+        };
+        self.previous = delay;
+
+        if policy.jitter && policy.strategy != BackoffStrategy::DecorrelatedJitter {
+            Some(delay + self.random_between(Duration::ZERO, policy.initial_delay))
+        } else {
+            Some(delay)
+        }
+    }
+
+    /// Forget past failures after a healthy connection
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.previous = self.policy.initial_delay;
+    }
+
+    /// Failed attempts since the last reset
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Whether a connection that stayed up for `uptime` resets the schedule
+    pub fn is_healthy(&self, uptime: Duration) -> bool {
+        uptime >= self.policy.reset_after
+    }
+
+    fn random_between(&mut self, low: Duration, high: Duration) -> Duration {
+        if high <= low {
+            return low;
+        }
+        let span = (high - low).as_millis() as u64;
+        low + Duration::from_millis(self.rng.u64(0..span))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(strategy: BackoffStrategy, max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            strategy,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            max_retries,
+            jitter: false,
+            reset_after: Duration::from_secs(60),
+        }
+    }
+
+    fn delays(backoff: &mut Backoff, n: usize) -> Vec<u64> {
+        (0..n)
+            .map_while(|_| backoff.next_delay())
+            .map(|d| d.as_secs())
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_and_exponential_delays() {
+        let mut fixed = policy(BackoffStrategy::Fixed, 0).backoff();
+        assert_eq!(delays(&mut fixed, 4), vec![1, 1, 1, 1]);
+
+        let mut exponential = policy(BackoffStrategy::Exponential, 0).backoff();
+        assert_eq!(delays(&mut exponential, 6), vec![1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn test_max_retries_gives_up_until_reset() {
+        let mut backoff = policy(BackoffStrategy::Exponential, 3).backoff();
+        assert_eq!(delays(&mut backoff, 5), vec![1, 2, 4]);
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.failures(), 3);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_stays_in_bounds() {
+        let mut backoff = policy(BackoffStrategy::DecorrelatedJitter, 0).backoff();
+        let mut previous = Duration::from_secs(1);
+        for _ in 0..100 {
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay >= Duration::from_secs(1));
+            assert!(delay <= (previous * 3).min(Duration::from_secs(10)));
+            previous = delay;
+        }
+    }
+}
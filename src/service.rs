@@ -1,7 +1,8 @@
 use crate::config::{AppConfig, ChannelTypeParams};
 use crate::error::{AppError, Result};
+use crate::metrics::ChannelConnections;
 use crate::port_check::check_ports;
-use crate::ssh::SshManager;
+use crate::ssh::{ChannelState, ChannelStatus, SshManager};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{error, info, warn};
@@ -94,11 +95,17 @@ impl ServiceManager {
         info!("Found {} channel(s) to start", channels.len());
 
         for channel_config in channels {
-            let mut manager =
-                SshManager::new(channel_config.clone(), self.config.reconnection.clone());
+            let mut manager = SshManager::new(channel_config.clone());
 
             match manager.start().await {
                 Ok(_) => {
+                    // user@host of each candidate, preferred first
+                    let via = channel_config
+                        .hosts
+                        .iter()
+                        .map(|h| format!("{}@{}", h.username, h.host))
+                        .collect::<Vec<_>>()
+                        .join(" | ");
                     match &channel_config.params {
                         ChannelTypeParams::ForwardedTcpIp {
                             remote_bind_port,
@@ -108,12 +115,8 @@ impl ServiceManager {
                             let local_dest =
                                 format!("{}:{}", local_connect_host, local_connect_port);
                             println!(
-                                "✓ Channel '{}' started: remote:{} -> local {} ({}@{})",
-                                channel_config.name,
-                                remote_bind_port,
-                                local_dest,
-                                channel_config.username,
-                                channel_config.host
+                                "✓ Channel '{}' started: remote:{} -> local {} ({})",
+                                channel_config.name, remote_bind_port, local_dest, via
                             );
                         }
                         ChannelTypeParams::DirectTcpIp {
@@ -124,18 +127,20 @@ impl ServiceManager {
                         } => {
                             let dest_info = format!("{}:{}", dest_host, dest_port);
                             println!(
-                                "✓ Channel '{}' started: local:{} -> {} -> {}@{}",
-                                channel_config.name,
-                                local_port,
-                                dest_info,
-                                channel_config.username,
-                                channel_config.host
+                                "✓ Channel '{}' started: local:{} -> {} -> {}",
+                                channel_config.name, local_port, dest_info, via
+                            );
+                        }
+                        ChannelTypeParams::Dynamic { local_port, .. } => {
+                            println!(
+                                "✓ Channel '{}' started: SOCKS5 local:{} -> {}",
+                                channel_config.name, local_port, via
                             );
                         }
                         ChannelTypeParams::Session { .. } => {
                             println!(
-                                "✓ Channel '{}' started (session) ({}@{})",
-                                channel_config.name, channel_config.username, channel_config.host
+                                "✓ Channel '{}' started (session) ({})",
+                                channel_config.name, via
                             );
                         }
                     }
@@ -236,17 +241,38 @@ impl ServiceManager {
     //     self.start().await
     // }
 
+    /// Totals and active relays of a running channel, `None` if no such channel runs
+    pub async fn connections(&self, channel: &str) -> Option<ChannelConnections> {
+        let managers = self.managers.lock().await;
+        managers
+            .iter()
+            .find(|m| m.name() == channel)
+            .map(|m| m.metrics().connections(channel))
+    }
+
+    /// Prometheus exposition of every running channel's counters
+    pub async fn metrics(&self) -> String {
+        let managers = self.managers.lock().await;
+        let channels: Vec<_> = managers.iter().map(|m| (m.status(), m.metrics())).collect();
+        crate::metrics::render(&channels)
+    }
+
     /// Get service status
     pub async fn status(&self) -> ServiceStatus {
         let state = self.state.lock().await.clone();
         let managers = self.managers.lock().await;
-        let channel_count = managers.len();
+        let channels: Vec<ChannelStatus> = managers.iter().map(SshManager::status).collect();
+        let active_channels = channels
+            .iter()
+            .filter(|c| c.state == ChannelState::Connected)
+            .count();
         let total_channels = self.config.channels.len();
 
         ServiceStatus {
             state,
-            active_channels: channel_count,
+            active_channels,
             total_channels,
+            channels,
         }
     }
 }
@@ -257,6 +283,8 @@ pub struct ServiceStatus {
     pub state: ServiceState,
     pub active_channels: usize,
     pub total_channels: usize,
+    /// Per-channel state of the running managers
+    pub channels: Vec<ChannelStatus>,
 }
 
 impl std::fmt::Display for ServiceStatus {
@@ -0,0 +1,147 @@
+use crate::error::{AppError, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const SOCKS_VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_NOT_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// SOCKS5 reply codes used by the dynamic forwarder
+pub const REPLY_SUCCEEDED: u8 = 0x00;
+pub const REPLY_GENERAL_FAILURE: u8 = 0x01;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REPLY_ADDRESS_TYPE_NOT_SUPPORTED: u8 = 0x08;
+
+/// Perform the SOCKS5 greeting and read a CONNECT request (no authentication).
+/// Returns the requested destination; the caller must answer with [`send_reply`].
+pub async fn accept_connect<S>(stream: &mut S) -> Result<(String, u16)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != SOCKS_VERSION {
+        return Err(AppError::SshChannel(format!(
+            "Unsupported SOCKS version {}",
+            header[0]
+        )));
+    }
+
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&METHOD_NO_AUTH) {
+        stream
+            .write_all(&[SOCKS_VERSION, METHOD_NOT_ACCEPTABLE])
+            .await?;
+        return Err(AppError::SshChannel(
+            "SOCKS client does not offer no-auth method".to_string(),
+        ));
+    }
+    stream.write_all(&[SOCKS_VERSION, METHOD_NO_AUTH]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != CMD_CONNECT {
+        send_reply(stream, REPLY_COMMAND_NOT_SUPPORTED).await?;
+        return Err(AppError::SshChannel(format!(
+            "Unsupported SOCKS command {}",
+            request[1]
+        )));
+    }
+
+    let host = match request[3] {
+        ATYP_IPV4 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            std::net::Ipv4Addr::from(addr).to_string()
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            let mut name = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name)
+                .map_err(|_| AppError::SshChannel("Invalid SOCKS domain name".to_string()))?
+        }
+        ATYP_IPV6 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            std::net::Ipv6Addr::from(addr).to_string()
+        }
+        other => {
+            send_reply(stream, REPLY_ADDRESS_TYPE_NOT_SUPPORTED).await?;
+            return Err(AppError::SshChannel(format!(
+                "Unsupported SOCKS address type {}",
+                other
+            )));
+        }
+    };
+
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+
+    Ok((host, u16::from_be_bytes(port)))
+}
+
+/// Send a SOCKS5 reply with an unspecified bound address
+pub async fn send_reply<S>(stream: &mut S, code: u8) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    stream
+        .write_all(&[SOCKS_VERSION, code, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_domain_request() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let client_task = tokio::spawn(async move {
+            client.write_all(&[5, 1, 0]).await.unwrap();
+            let mut choice = [0u8; 2];
+            client.read_exact(&mut choice).await.unwrap();
+            assert_eq!(choice, [5, 0]);
+
+            let mut req = vec![5, 1, 0, 3, 11];
+            req.extend_from_slice(b"example.com");
+            req.extend_from_slice(&443u16.to_be_bytes());
+            client.write_all(&req).await.unwrap();
+
+            let mut reply = [0u8; 10];
+            client.read_exact(&mut reply).await.unwrap();
+            reply[1]
+        });
+
+        let target = accept_connect(&mut server).await.unwrap();
+        assert_eq!(target, ("example.com".to_string(), 443));
+        send_reply(&mut server, REPLY_SUCCEEDED).await.unwrap();
+
+        assert_eq!(client_task.await.unwrap(), REPLY_SUCCEEDED);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bind_command() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        tokio::spawn(async move {
+            client.write_all(&[5, 1, 0]).await.unwrap();
+            client
+                .write_all(&[5, 2, 0, 1, 127, 0, 0, 1, 0, 80])
+                .await
+                .unwrap();
+            let mut buf = [0u8; 12];
+            let _ = client.read(&mut buf).await;
+        });
+
+        assert!(accept_connect(&mut server).await.is_err());
+    }
+}
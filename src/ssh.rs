@@ -1,13 +1,27 @@
-use crate::config::{AuthConfig, ChannelConfig, ChannelTypeParams, ReconnectionConfig};
-use crate::error::{AppError, Result};
-use backon::{ExponentialBuilder, Retryable};
+use crate::audit::{self, AuditEntry};
+use crate::config::{
+    AuthConfig, AuthFailurePolicy, BalanceStrategy, ChannelConfig, ChannelTypeParams, HealthProbe,
+    HostEndpoint,
+};
+use crate::error::{AppError, ErrorKind, Result};
+use crate::events::{self, EventKind};
+use crate::health;
+use crate::hooks;
+use crate::metrics::{ActiveRelay, ChannelMetrics, RelayRecord};
+use crate::port_check;
+use crate::retry::RetryPolicy;
+use crate::socks;
 use russh::*;
 use russh_keys::key::KeyPair;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use tokio::sync::watch;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
@@ -34,6 +48,7 @@ struct ReverseForwardHandler {
     channel_name: String,
     local_host: String,
     local_port: u16,
+    metrics: Arc<ChannelMetrics>,
 }
 
 #[async_trait::async_trait]
@@ -52,154 +67,572 @@ impl client::Handler for ReverseForwardHandler {
         channel: russh::Channel<russh::client::Msg>,
         _connected_address: &str,
         _connected_port: u32,
-        _originator_address: &str,
-        _originator_port: u32,
+        originator_address: &str,
+        originator_port: u32,
         _session: &mut russh::client::Session,
     ) -> std::result::Result<(), Self::Error> {
         let local_addr = format!("{}:{}", self.local_host, self.local_port);
         let channel_name = self.channel_name.clone();
 
         match TcpStream::connect(&local_addr).await {
-            Ok(mut stream) => {
-                let mut channel_stream = channel.into_stream();
+            Ok(stream) => {
+                let relay = self.metrics.relay(
+                    format!("{}:{}", originator_address, originator_port),
+                    local_addr,
+                );
                 tokio::spawn(async move {
-                    if let Err(e) =
-                        tokio::io::copy_bidirectional(&mut stream, &mut channel_stream).await
-                    {
-                        debug!(channel = %channel_name, error = ?e, "Forwarded-tcpip relay ended");
-                    }
+                    relay_stream(&channel_name, "forwarded-tcpip", stream, channel, relay).await;
                 });
             }
             Err(e) => {
+                audit::record(AuditEntry::refused(
+                    &channel_name,
+                    "forwarded-tcpip",
+                    format!("{}:{}", originator_address, originator_port),
+                    local_addr.clone(),
+                    audit::Outcome::LocalConnectFailed,
+                    Some(e.to_string()),
+                ));
                 error!(
                     channel = %channel_name,
                     local = %local_addr,
                     error = ?e,
                     "Failed to connect to local address for forwarded-tcpip"
                 );
+                // Let the server drop the connection it is holding instead of leaving it open
+                let _ = channel.eof().await;
+                let _ = channel.close().await;
             }
         }
         Ok(())
     }
 }
 
+/// SSH session shared between the reconnect loop and the local listener's clients
+type SharedSession = Arc<client::Handle<ClientHandler>>;
+
+/// Live session of a channel, with the number of local clients it is relaying
+#[derive(Clone)]
+struct PooledSession {
+    handle: SharedSession,
+    relays: Arc<AtomicUsize>,
+}
+
+/// Sessions of a direct-tcpip or dynamic channel, one slot per configured session
+type SessionPool = Vec<Option<PooledSession>>;
+
+/// Picks the session that serves each new local client
+struct SessionPicker {
+    balance: BalanceStrategy,
+    next: AtomicUsize,
+}
+
+impl SessionPicker {
+    fn new(balance: BalanceStrategy) -> Self {
+        Self {
+            balance,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn pick(&self, pool: &SessionPool) -> Option<PooledSession> {
+        let live: Vec<&PooledSession> = pool
+            .iter()
+            .flatten()
+            .filter(|s| !s.handle.is_closed())
+            .collect();
+        if live.is_empty() {
+            return None;
+        }
+        let chosen = match self.balance {
+            BalanceStrategy::RoundRobin => {
+                live[self.next.fetch_add(1, Ordering::Relaxed) % live.len()]
+            }
+            BalanceStrategy::LeastConnections => live
+                .iter()
+                .min_by_key(|s| s.relays.load(Ordering::Relaxed))
+                .copied()?,
+        };
+        Some(chosen.clone())
+    }
+}
+
+/// Counts a client against its session while it is being relayed
+struct RelayGuard(Arc<AtomicUsize>);
+
+impl RelayGuard {
+    fn new(relays: &Arc<AtomicUsize>) -> Self {
+        relays.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(relays))
+    }
+}
+
+impl Drop for RelayGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// How a connection attempt ended without error
+enum Outcome {
+    Closed,
+    /// Dropped in favour of the preferred host, which is reachable again
+    Failback,
+}
+
+/// Lifecycle state of one channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelState {
+    Connecting,
+    Connected,
+    /// Waiting to retry after a failure
+    Reconnecting,
+    /// `max_retries` exhausted (or `auth_failure = "stop"`); stays down until restart
+    GaveUp,
+    Stopped,
+}
+
+impl ChannelState {
+    /// Which state of a channel's sessions the channel as a whole reports
+    fn rank(self) -> u8 {
+        match self {
+            ChannelState::Connected => 4,
+            ChannelState::Connecting => 3,
+            ChannelState::Reconnecting => 2,
+            ChannelState::GaveUp => 1,
+            ChannelState::Stopped => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChannelState::Connecting => "connecting",
+            ChannelState::Connected => "connected",
+            ChannelState::Reconnecting => "reconnecting",
+            ChannelState::GaveUp => "gave-up",
+            ChannelState::Stopped => "stopped",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Live status of one channel, reported by `status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelStatus {
+    pub name: String,
+    pub state: ChannelState,
+    /// Consecutive failed attempts since the last healthy connection
+    pub failures: u32,
+    /// Last error, kept until the next successful connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+    /// Host of the current (or last attempted) connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_host: Option<String>,
+    /// Configured parallel sessions, and how many of them are connected
+    #[serde(default)]
+    pub sessions: usize,
+    #[serde(default)]
+    pub sessions_up: usize,
+    /// When the current connection was established
+    #[serde(skip)]
+    pub connected_since: Option<Instant>,
+    /// Latest `health_check` result of the current connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthStatus>,
+}
+
+/// Outcome of a channel's latest health check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthStatus {
+    /// Probe name (tcp, banner, http, postgres, redis, mysql)
+    pub probe: String,
+    pub healthy: bool,
+    /// Failed checks in a row
+    pub failures: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    /// Unix time of the check, in seconds
+    pub checked_at: u64,
+}
+
+impl ChannelStatus {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: ChannelState::Connecting,
+            failures: 0,
+            reason: None,
+            error_kind: None,
+            active_host: None,
+            sessions: 1,
+            sessions_up: 0,
+            connected_since: None,
+            health: None,
+        }
+    }
+}
+
 /// SSH connection manager
 pub struct SshManager {
     config: ChannelConfig,
-    reconnection_config: ReconnectionConfig,
-    shutdown_tx: Option<mpsc::Sender<()>>,
+    /// Status of each session (one unless `sessions` is set)
+    slots: Vec<Arc<watch::Sender<ChannelStatus>>>,
+    metrics: Arc<ChannelMetrics>,
     cancellation_token: Option<CancellationToken>,
 }
 
 impl SshManager {
-    /// Create a new SSH manager
-    pub fn new(config: ChannelConfig, reconnection_config: ReconnectionConfig) -> Self {
+    /// Create a new SSH manager; reconnection settings come from `config.reconnection`
+    pub fn new(config: ChannelConfig) -> Self {
+        let slots = (0..config.sessions)
+            .map(|_| Arc::new(watch::channel(ChannelStatus::new(&config.name)).0))
+            .collect();
         Self {
             config,
-            reconnection_config,
-            shutdown_tx: None,
+            slots,
+            metrics: Arc::default(),
             cancellation_token: None,
         }
     }
 
+    /// Current status of the managed channel: that of its best session, with the number of
+    /// sessions up
+    pub fn status(&self) -> ChannelStatus {
+        let slots: Vec<ChannelStatus> = self.slots.iter().map(|s| s.borrow().clone()).collect();
+        let mut best = slots[0].clone();
+        for slot in &slots[1..] {
+            if slot.state.rank() > best.state.rank() {
+                best = slot.clone();
+            }
+        }
+        best.sessions = slots.len();
+        best.sessions_up = slots
+            .iter()
+            .filter(|s| s.state == ChannelState::Connected)
+            .count();
+        best
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Counters of the channel, shared with its running tasks
+    pub fn metrics(&self) -> Arc<ChannelMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
     /// Start managing the SSH connection and channel
     pub async fn start(&mut self) -> Result<()> {
-        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         let cancel = CancellationToken::new();
         self.cancellation_token = Some(cancel.clone());
-        self.shutdown_tx = Some(shutdown_tx);
 
-        let config = self.config.clone();
-        let reconnection_config = self.reconnection_config.clone();
+        // Local listeners are bound once and survive reconnects
+        let (pool, pool_rx) = watch::channel(vec![None; self.config.sessions]);
+        match &self.config.params {
+            ChannelTypeParams::DirectTcpIp {
+                listen_host,
+                local_port,
+                ..
+            }
+            | ChannelTypeParams::Dynamic {
+                listen_host,
+                local_port,
+            } => {
+                let listener = bind_listener(listen_host, *local_port).await?;
+                tokio::spawn(run_local_listener(
+                    listener,
+                    self.config.clone(),
+                    pool_rx,
+                    Arc::clone(&self.metrics),
+                    cancel.clone(),
+                ));
+            }
+            _ => drop(pool_rx),
+        }
 
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    _ = shutdown_rx.recv() => {
-                        info!(channel = %config.name, "Shutting down SSH manager");
-                        break;
+        if !self.config.hooks.is_empty() {
+            tokio::spawn(hooks::run(
+                self.config.clone(),
+                events::subscribe(),
+                cancel.clone(),
+            ));
+        }
+
+        let pool = Arc::new(pool);
+        for (slot, status) in self.slots.iter().enumerate() {
+            let mut config = self.config.clone();
+            // Session N prefers the Nth candidate host, so several sessions spread over them
+            let candidates = config.hosts.len();
+            config.hosts.rotate_left(slot % candidates);
+            tokio::spawn(Self::run_session(
+                config,
+                slot,
+                Arc::clone(status),
+                Arc::clone(&pool),
+                Arc::clone(&self.metrics),
+                cancel.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Keep session `slot` of the channel connected until cancelled or given up
+    async fn run_session(
+        config: ChannelConfig,
+        slot: usize,
+        status: Arc<watch::Sender<ChannelStatus>>,
+        pool: Arc<watch::Sender<SessionPool>>,
+        metrics: Arc<ChannelMetrics>,
+        cancel: CancellationToken,
+    ) {
+        let mut backoff = RetryPolicy::from_config(&config.reconnection).backoff();
+        let failback_interval = Duration::from_secs(config.reconnection.failback_interval_secs);
+        // Candidates excluded after a permanent error (bad key, host key, auth with "stop")
+        let mut disabled = vec![false; config.hosts.len()];
+        let mut current = 0;
+        let mut attempted = false;
+        loop {
+            if attempted {
+                ChannelMetrics::inc(&metrics.reconnects);
+            }
+            attempted = true;
+            let endpoint = &config.hosts[current];
+            let preferred = disabled.iter().position(|d| !d).unwrap_or(0);
+            status.send_modify(|s| {
+                s.connected_since = None;
+                s.health = None;
+                s.active_host = Some(endpoint.name.clone());
+                if s.state != ChannelState::Reconnecting {
+                    s.state = ChannelState::Connecting;
+                }
+            });
+            events::emit(
+                &config.name,
+                EventKind::ChannelConnecting {
+                    host: endpoint.name.clone(),
+                    session: slot,
+                },
+            );
+            let result = tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = preferred_host_back(&config.hosts[preferred], failback_interval, &status),
+                    if current != preferred && !failback_interval.is_zero() => Ok(Outcome::Failback),
+                result = Self::establish_connection(&config, endpoint, &status, &pool, slot, &metrics, cancel.clone()) => {
+                    result.map(|_| Outcome::Closed)
+                }
+            };
+            pool.send_modify(|p| p[slot] = None);
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let uptime = status.borrow().connected_since.map(|t| t.elapsed());
+            if uptime.is_some_and(|uptime| backoff.is_healthy(uptime)) {
+                backoff.reset();
+            }
+            let ended = match &result {
+                Ok(Outcome::Failback) => EventKind::ChannelDisconnected {
+                    host: endpoint.name.clone(),
+                    session: slot,
+                    reason: format!("failing back to {}", config.hosts[preferred].name),
+                },
+                Ok(Outcome::Closed) => EventKind::ChannelDisconnected {
+                    host: endpoint.name.clone(),
+                    session: slot,
+                    reason: "connection closed".to_string(),
+                },
+                Err(e) if uptime.is_some() => EventKind::ChannelDisconnected {
+                    host: endpoint.name.clone(),
+                    session: slot,
+                    reason: e.to_string(),
+                },
+                Err(e) => EventKind::ChannelFailed {
+                    host: endpoint.name.clone(),
+                    session: slot,
+                    kind: e.kind(),
+                    error: e.to_string(),
+                },
+            };
+            events::emit(&config.name, ended);
+
+            let mut delay_override = None;
+            match result {
+                Ok(Outcome::Failback) => {
+                    info!(
+                        channel = %config.name,
+                        from = %endpoint.name,
+                        to = %config.hosts[preferred].name,
+                        "Preferred host is reachable again, failing back"
+                    );
+                    current = preferred;
+                    continue;
+                }
+                Ok(Outcome::Closed) => {
+                    warn!(channel = %config.name, host = %endpoint.name, "Connection closed unexpectedly")
+                }
+                Err(e) => {
+                    let kind = e.kind();
+                    let reason = e.to_string();
+                    if kind == ErrorKind::AuthRejected {
+                        ChannelMetrics::inc(&metrics.auth_failures);
                     }
-                    _ = cancel.cancelled() => break,
-                    result = Self::connect_and_manage_channel(&config, &reconnection_config, cancel.clone()) => {
-                        match result {
-                            Ok(_) => {
-                                warn!(channel = %config.name, "Connection closed unexpectedly");
-                            }
-                            Err(e) => {
-                                error!(channel = %config.name, error = ?e, "Connection error");
+                    // Log a repeated failure once at error level, then quietly
+                    let repeated = status.borrow().reason.as_deref() == Some(reason.as_str());
+                    if repeated {
+                        debug!(channel = %config.name, host = %endpoint.name, kind = %kind, error = %reason, "Connection failed again");
+                    } else {
+                        error!(channel = %config.name, host = %endpoint.name, kind = %kind, error = %reason, "Connection failed");
+                    }
+                    status.send_modify(|s| {
+                        s.reason = Some(reason);
+                        s.error_kind = Some(kind);
+                    });
+
+                    let permanent = match kind {
+                        ErrorKind::Transient => false,
+                        ErrorKind::AuthRejected => match config.reconnection.auth_failure {
+                            AuthFailurePolicy::Retry => false,
+                            AuthFailurePolicy::Backoff => {
+                                delay_override = Some(Duration::from_secs(
+                                    config.reconnection.auth_failure_delay_secs,
+                                ));
+                                false
                             }
+                            AuthFailurePolicy::Stop => true,
+                        },
+                        ErrorKind::Config | ErrorKind::HostKeyMismatch => true,
+                    };
+                    if permanent {
+                        disabled[current] = true;
+                        if disabled.iter().all(|d| *d) {
+                            error!(
+                                channel = %config.name,
+                                kind = %kind,
+                                "Not retrying: fix the configuration and restart the service"
+                            );
+                            status.send_modify(|s| {
+                                s.state = ChannelState::GaveUp;
+                                s.failures = backoff.failures() + 1;
+                            });
+                            events::emit(
+                                &config.name,
+                                EventKind::ChannelGaveUp {
+                                    session: slot,
+                                    failures: backoff.failures() + 1,
+                                },
+                            );
+                            break;
+                        }
+                        warn!(channel = %config.name, host = %endpoint.name, kind = %kind, "Skipping host until restart");
+                    }
+
+                    // Fail over to the next candidate right away; back off once all were tried
+                    if uptime.is_none() {
+                        if let Some(next) =
+                            (current + 1..config.hosts.len()).find(|&i| !disabled[i])
+                        {
+                            info!(
+                                channel = %config.name,
+                                from = %endpoint.name,
+                                to = %config.hosts[next].name,
+                                "Failing over to next host"
+                            );
+                            current = next;
+                            continue;
                         }
                     }
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
             }
-        });
+            current = disabled.iter().position(|d| !d).unwrap_or(0);
 
-        Ok(())
+            let Some(delay) = backoff.next_delay() else {
+                error!(
+                    channel = %config.name,
+                    attempts = backoff.failures() + 1,
+                    "Giving up after max_retries ({}) reconnection attempts",
+                    config.reconnection.max_retries
+                );
+                status.send_modify(|s| {
+                    s.state = ChannelState::GaveUp;
+                    s.failures = backoff.failures() + 1;
+                });
+                events::emit(
+                    &config.name,
+                    EventKind::ChannelGaveUp {
+                        session: slot,
+                        failures: backoff.failures() + 1,
+                    },
+                );
+                break;
+            };
+            let delay = delay_override.unwrap_or(delay);
+            status.send_modify(|s| {
+                s.state = ChannelState::Reconnecting;
+                s.failures = backoff.failures();
+            });
+            info!(
+                channel = %config.name,
+                failures = backoff.failures(),
+                "Reconnecting in {:.1}s",
+                delay.as_secs_f64()
+            );
+
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+        status.send_modify(|s| {
+            s.connected_since = None;
+            if s.state != ChannelState::GaveUp {
+                s.state = ChannelState::Stopped;
+            }
+        });
     }
 
     /// Stop the SSH manager
     pub async fn stop(&mut self) -> Result<()> {
-        if let Some(tx) = self.shutdown_tx.take() {
-            let _ = tx.send(()).await;
-        }
         if let Some(token) = self.cancellation_token.take() {
+            info!(channel = %self.config.name, "Shutting down SSH manager");
             token.cancel();
         }
         Ok(())
     }
 
-    /// Connect and manage SSH channel with reconnection logic
-    async fn connect_and_manage_channel(
+    /// Establish SSH connection and open channel
+    async fn establish_connection(
         config: &ChannelConfig,
-        reconnection_config: &ReconnectionConfig,
+        endpoint: &HostEndpoint,
+        status: &watch::Sender<ChannelStatus>,
+        pool: &watch::Sender<SessionPool>,
+        slot: usize,
+        metrics: &Arc<ChannelMetrics>,
         cancel: CancellationToken,
     ) -> Result<()> {
-        // Build retry policy
-        let builder = if reconnection_config.use_exponential_backoff {
-            let mut builder = ExponentialBuilder::default()
-                .with_min_delay(Duration::from_secs(reconnection_config.initial_delay_secs))
-                .with_max_delay(Duration::from_secs(reconnection_config.max_delay_secs));
-
-            if reconnection_config.max_retries > 0 {
-                builder = builder.with_max_times(reconnection_config.max_retries as usize);
-            }
-
-            builder
-        } else {
-            // For fixed interval, use exponential with same min/max delay
-            let mut builder = ExponentialBuilder::default()
-                .with_min_delay(Duration::from_secs(reconnection_config.initial_delay_secs))
-                .with_max_delay(Duration::from_secs(reconnection_config.initial_delay_secs));
-
-            if reconnection_config.max_retries > 0 {
-                builder = builder.with_max_times(reconnection_config.max_retries as usize);
-            }
-
-            builder
-        };
-
-        // Retry connection with backoff
-        (|| async { Self::establish_connection(config, cancel.clone()).await })
-            .retry(&builder)
-            .await
-            .map_err(|e| AppError::SshConnection(format!("Failed to establish connection: {}", e)))
-    }
-
-    /// Establish SSH connection and open channel
-    async fn establish_connection(config: &ChannelConfig, cancel: CancellationToken) -> Result<()> {
         info!(
             channel = %config.name,
-            host = %config.host,
-            port = config.port,
+            session = slot,
+            host = %endpoint.host,
+            port = endpoint.port,
             "Establishing SSH connection"
         );
 
         if let ChannelTypeParams::ForwardedTcpIp { .. } = &config.params {
-            return run_forwarded_tcpip(config, cancel).await;
+            return run_forwarded_tcpip(config, endpoint, status, metrics, cancel).await;
         }
 
-        let mut session = connect_and_authenticate(config, ClientHandler).await?;
+        let mut session =
+            connect_and_authenticate(config, endpoint, ClientHandler, metrics).await?;
+        mark_connected(status, slot);
 
         info!(channel = %config.name, "Opening channel");
 
@@ -207,12 +640,28 @@ impl SshManager {
             ChannelTypeParams::Session { .. } => {
                 open_session_channel(&mut session, config).await?;
                 info!(channel = %config.name, "Channel opened successfully");
-                loop {
-                    tokio::time::sleep(Duration::from_secs(30)).await;
+                tokio::select! {
+                    _ = cancel.cancelled() => Ok(()),
+                    _ = session_closed(&session) => Err(session_lost()),
                 }
             }
-            ChannelTypeParams::DirectTcpIp { .. } => {
-                return run_direct_tcpip_listener(&mut session, config, cancel).await;
+            ChannelTypeParams::DirectTcpIp { .. } | ChannelTypeParams::Dynamic { .. } => {
+                // The listener lives in `run_local_listener`; hand it this session until it ends
+                let session = PooledSession {
+                    handle: Arc::new(session),
+                    relays: Arc::default(),
+                };
+                pool.send_modify(|p| p[slot] = Some(session.clone()));
+                let result = tokio::select! {
+                    _ = cancel.cancelled() => Ok(()),
+                    _ = session_closed(&session.handle) => {
+                        warn!(channel = %config.name, session = slot, "SSH session lost");
+                        Err(session_lost())
+                    }
+                    e = health_loop(&session.handle, config, status) => Err(e),
+                };
+                pool.send_modify(|p| p[slot] = None);
+                result
             }
             ChannelTypeParams::ForwardedTcpIp { .. } => Err(AppError::SshChannel(
                 "forwarded-tcpip should be handled earlier".to_string(),
@@ -221,8 +670,74 @@ impl SshManager {
     }
 }
 
+/// How long `test` waits on a round trip through a remote forward without a health check
+const ROUND_TRIP_WAIT: Duration = Duration::from_secs(2);
+
+/// Check a remote forward end to end: over a new session to `endpoint`, connect to the port
+/// the channel has bound on the server. The server hands that connection back to the running
+/// service, which relays it to the local target. The server accepts the connection itself, so
+/// only an answer proves the round trip: the channel's health check probe when it speaks the
+/// local service's protocol (not `tcp`, and aimed at this forward), otherwise any data (e.g. a
+/// banner). It waits for the health check's `timeout`, or [`ROUND_TRIP_WAIT`] without one.
+/// A service that waits for the client to speak first needs such a `health_check` to pass.
+pub async fn check_remote_forward(
+    config: &ChannelConfig,
+    endpoint: &HostEndpoint,
+) -> std::result::Result<(), String> {
+    let ChannelTypeParams::ForwardedTcpIp {
+        remote_bind_port, ..
+    } = &config.params
+    else {
+        return Err("not a remote forward".to_string());
+    };
+    let session =
+        connect_and_authenticate(config, endpoint, ClientHandler, &ChannelMetrics::default())
+            .await
+            .map_err(|e| format!("cannot reach {}: {}", endpoint.name, e))?;
+    let result = match session
+        .channel_open_direct_tcpip("127.0.0.1", *remote_bind_port as u32, "127.0.0.1", 0)
+        .await
+    {
+        Ok(channel) => {
+            let mut stream = channel.into_stream();
+            let wait = config
+                .health_check
+                .as_ref()
+                .map_or(ROUND_TRIP_WAIT, |check| check.timeout);
+            let probe = config.health_check.as_ref().filter(|check| {
+                check.probe != HealthProbe::Tcp
+                    && check.port == *remote_bind_port
+                    && matches!(check.host.as_str(), "127.0.0.1" | "localhost" | "::1")
+            });
+            let answer = async {
+                match probe {
+                    Some(check) => health::probe(&check.probe, &check.host, &mut stream).await,
+                    None => port_check::stream_answers(&mut stream, wait).await,
+                }
+            };
+            tokio::time::timeout(wait, answer)
+                .await
+                .unwrap_or_else(|_| Err(format!("no answer within {}s", wait.as_secs())))
+        }
+        Err(e) => Err(format!(
+            "{} refused a connection to its port {}: {}",
+            endpoint.name, remote_bind_port, e
+        )),
+    };
+    let _ = session
+        .disconnect(Disconnect::ByApplication, "", "en")
+        .await;
+    result
+}
+
 /// Run remote port forwarding (ssh -R style): ask server to bind a port, bridge incoming connections to local.
-async fn run_forwarded_tcpip(config: &ChannelConfig, cancel: CancellationToken) -> Result<()> {
+async fn run_forwarded_tcpip(
+    config: &ChannelConfig,
+    endpoint: &HostEndpoint,
+    status: &watch::Sender<ChannelStatus>,
+    metrics: &Arc<ChannelMetrics>,
+    cancel: CancellationToken,
+) -> Result<()> {
     let ChannelTypeParams::ForwardedTcpIp {
         remote_bind_port,
         local_connect_host,
@@ -238,9 +753,10 @@ async fn run_forwarded_tcpip(config: &ChannelConfig, cancel: CancellationToken)
         channel_name: config.name.clone(),
         local_host: local_connect_host.clone(),
         local_port: *local_connect_port,
+        metrics: Arc::clone(metrics),
     };
 
-    let mut session = connect_and_authenticate(config, handler).await?;
+    let mut session = connect_and_authenticate(config, endpoint, handler, metrics).await?;
 
     info!(channel = %config.name, "Requesting remote port forward (tcpip-forward)");
 
@@ -261,72 +777,227 @@ async fn run_forwarded_tcpip(config: &ChannelConfig, cancel: CancellationToken)
         local = %format!("{}:{}", local_connect_host, local_connect_port),
         "Remote forward active (incoming connections will be bridged to local)"
     );
+    // Remote forwards always run a single session
+    mark_connected(status, 0);
 
     tokio::select! {
         _ = cancel.cancelled() => {
             info!(channel = %config.name, "Forward cancelled");
             Ok(())
         }
-        result = &mut session => {
-            result.map_err(|e| AppError::SshConnection(format!("Session ended: {}", e)))
+        _ = session_closed(&session) => Err(session_lost()),
+        e = health_loop(&session, config, status) => Err(e),
+    }
+}
+
+/// Resolves once the channel is connected (to a fallback host) and `preferred` accepts TCP
+/// connections again, checked every `interval`
+async fn preferred_host_back(
+    preferred: &HostEndpoint,
+    interval: Duration,
+    status: &watch::Sender<ChannelStatus>,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if status.borrow().state != ChannelState::Connected {
+            continue;
+        }
+        let probe = TcpStream::connect((preferred.host.as_str(), preferred.port));
+        if let Ok(Ok(_)) = tokio::time::timeout(FAILBACK_PROBE_TIMEOUT, probe).await {
+            return;
         }
     }
 }
 
+/// How often listeners check whether their SSH session is still alive
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Connect timeout when probing the preferred host for failback
+const FAILBACK_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Run the channel's health check through `session` right away and then every interval,
+/// recording each result in `status`. Resolves with the error to reconnect with once
+/// `reconnect_after` checks in a row failed; never resolves without a health check.
+async fn health_loop<H: client::Handler>(
+    session: &client::Handle<H>,
+    config: &ChannelConfig,
+    status: &watch::Sender<ChannelStatus>,
+) -> AppError {
+    let Some(check) = &config.health_check else {
+        return std::future::pending().await;
+    };
+    let mut tick = tokio::time::interval(check.interval);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut failures = 0u32;
+    loop {
+        tick.tick().await;
+        let started = Instant::now();
+        let result = tokio::time::timeout(check.timeout, async {
+            let channel = session
+                .channel_open_direct_tcpip(check.host.as_str(), check.port as u32, "127.0.0.1", 0)
+                .await
+                .map_err(|e| format!("cannot open {}:{}: {}", check.host, check.port, e))?;
+            let mut stream = channel.into_stream();
+            health::probe(&check.probe, &check.host, &mut stream).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(format!("no answer within {}s", check.timeout.as_secs())));
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match &result {
+            Ok(()) if failures > 0 => {
+                info!(channel = %config.name, probe = check.probe.name(), "Health check passing again")
+            }
+            Ok(()) => {
+                debug!(channel = %config.name, probe = check.probe.name(), latency_ms, "Health check passed")
+            }
+            Err(e) if failures == 0 => {
+                warn!(channel = %config.name, probe = check.probe.name(), error = %e, "Health check failed")
+            }
+            Err(e) => {
+                debug!(channel = %config.name, probe = check.probe.name(), error = %e, "Health check failed again")
+            }
+        }
+        failures = if result.is_ok() { 0 } else { failures + 1 };
+        let checked_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        status.send_modify(|s| {
+            s.health = Some(HealthStatus {
+                probe: check.probe.name().to_string(),
+                healthy: result.is_ok(),
+                failures,
+                error: result.as_ref().err().cloned(),
+                latency_ms,
+                checked_at,
+            })
+        });
+
+        if let Err(e) = result {
+            if check.reconnect_after > 0 && failures >= check.reconnect_after {
+                warn!(channel = %config.name, failures, "Health check keeps failing, reconnecting");
+                return AppError::SshChannel(format!(
+                    "health check failed {} times in a row: {}",
+                    failures, e
+                ));
+            }
+        }
+    }
+}
+
+/// Resolves once the SSH session has ended (transport error, keepalive timeout or disconnect)
+async fn session_closed<H: client::Handler>(session: &client::Handle<H>) {
+    let mut tick = tokio::time::interval(SESSION_CHECK_INTERVAL);
+    while !session.is_closed() {
+        tick.tick().await;
+    }
+}
+
+fn session_lost() -> AppError {
+    AppError::SshConnection("SSH session closed (connection lost or keepalive timeout)".to_string())
+}
+
+fn mark_connected(status: &watch::Sender<ChannelStatus>, slot: usize) {
+    status.send_modify(|s| {
+        s.state = ChannelState::Connected;
+        s.reason = None;
+        s.error_kind = None;
+        s.connected_since = Some(Instant::now());
+    });
+    let s = status.borrow();
+    events::emit(
+        &s.name,
+        EventKind::ChannelConnected {
+            host: s.active_host.clone().unwrap_or_default(),
+            session: slot,
+        },
+    );
+}
+
 /// Connect to the SSH server and authenticate. Returns an authenticated `client::Handle<H>`.
 async fn connect_and_authenticate<H>(
     config: &ChannelConfig,
+    endpoint: &HostEndpoint,
     handler: H,
+    metrics: &ChannelMetrics,
 ) -> Result<client::Handle<H>>
 where
-    H: client::Handler + Send + 'static,
+    H: client::Handler<Error = russh::Error> + Send + 'static,
 {
-    let mut config_builder = russh::client::Config::default();
-    config_builder.keepalive_interval = Some(Duration::from_secs(15));
-    config_builder.keepalive_max = 3;
+    let started = Instant::now();
+    let config_builder = russh::client::Config {
+        keepalive_interval: endpoint.keepalive_interval,
+        keepalive_max: endpoint.keepalive_max,
+        ..Default::default()
+    };
     let config_arc = Arc::new(config_builder);
 
     let mut session =
-        russh::client::connect(config_arc, (config.host.as_str(), config.port), handler)
+        russh::client::connect(config_arc, (endpoint.host.as_str(), endpoint.port), handler)
             .await
-            .map_err(|e| AppError::SshConnection(format!("Failed to connect: {:?}", e)))?;
+            .map_err(|e| match e {
+                russh::Error::UnknownKey
+                | russh::Error::WrongServerSig
+                | russh::Error::KeyChanged { .. } => AppError::HostKey(format!(
+                    "Host key of {}:{} not accepted: {}",
+                    endpoint.host, endpoint.port, e
+                )),
+                e => AppError::SshConnection(format!("Failed to connect: {:?}", e)),
+            })?;
 
     info!(channel = %config.name, "SSH connection established, authenticating");
 
-    match &config.auth {
+    match &endpoint.auth {
         AuthConfig::Password { password } => {
-            session
-                .authenticate_password(&config.username, password)
+            let accepted = session
+                .authenticate_password(&endpoint.username, password)
                 .await
                 .map_err(|e| {
-                    AppError::SshAuthentication(format!("Password authentication failed: {}", e))
+                    AppError::SshConnection(format!("Password authentication failed: {}", e))
                 })?;
+            if !accepted {
+                return Err(AppError::SshAuthentication(format!(
+                    "Password rejected for user '{}'",
+                    endpoint.username
+                )));
+            }
         }
         AuthConfig::Key {
             key_path,
             passphrase,
         } => {
             let key = load_secret_key(key_path, passphrase.as_deref()).await?;
-            session
-                .authenticate_publickey(&config.username, Arc::new(key))
+            let accepted = session
+                .authenticate_publickey(&endpoint.username, Arc::new(key))
                 .await
                 .map_err(|e| {
-                    AppError::SshAuthentication(format!("Key authentication failed: {}", e))
+                    AppError::SshConnection(format!("Key authentication failed: {}", e))
                 })?;
+            if !accepted {
+                return Err(AppError::SshAuthentication(format!(
+                    "Key {} rejected for user '{}'",
+                    key_path.display(),
+                    endpoint.username
+                )));
+            }
         }
     }
 
+    metrics.connect_latency.observe(started.elapsed());
     info!(channel = %config.name, "Authentication successful");
     Ok(session)
 }
 
 /// Load SSH private key
 async fn load_secret_key(key_path: &Path, passphrase: Option<&str>) -> Result<KeyPair> {
-    let key_path = key_path.to_path_buf();
+    let key_path = crate::ssh_config::expand_tilde(key_path)?;
     let passphrase = passphrase.map(|s| s.to_string());
 
     tokio::task::spawn_blocking(move || {
-        let key_data = std::fs::read_to_string(&key_path).map_err(AppError::Io)?;
+        let key_data = std::fs::read_to_string(&key_path).map_err(|e| {
+            AppError::Config(format!("Cannot read key {}: {}", key_path.display(), e))
+        })?;
 
         let key_result = if let Some(passphrase) = passphrase {
             russh_keys::decode_secret_key(&key_data, Some(&passphrase))
@@ -334,10 +1005,16 @@ async fn load_secret_key(key_path: &Path, passphrase: Option<&str>) -> Result<Ke
             russh_keys::decode_secret_key(&key_data, None)
         };
 
-        key_result.map_err(|e| AppError::SshAuthentication(format!("Failed to decode key: {}", e)))
+        key_result.map_err(|e| {
+            AppError::Config(format!(
+                "Failed to decode key {}: {}",
+                key_path.display(),
+                e
+            ))
+        })
     })
     .await
-    .map_err(|e| AppError::SshAuthentication(format!("Task join error: {}", e)))?
+    .map_err(|e| AppError::SshConnection(format!("Task join error: {}", e)))?
 }
 
 /// Open a session channel
@@ -395,86 +1072,284 @@ async fn open_session_channel(
     Ok(())
 }
 
-/// Run local TCP listener and forward each connection via a new direct-tcpip channel.
-async fn run_direct_tcpip_listener(
-    session: &mut client::Handle<ClientHandler>,
-    config: &ChannelConfig,
-    cancel: CancellationToken,
-) -> Result<()> {
-    let ChannelTypeParams::DirectTcpIp {
-        listen_host,
-        local_port,
-        dest_host,
-        dest_port,
-    } = &config.params
-    else {
-        return Err(AppError::SshChannel(
-            "run_direct_tcpip_listener expects DirectTcpIp params".to_string(),
-        ));
-    };
-
+/// Bind the local address of a direct-tcpip or dynamic channel
+async fn bind_listener(listen_host: &str, local_port: u16) -> Result<TcpListener> {
     let listen_addr = format!("{}:{}", listen_host, local_port);
-    let listener = TcpListener::bind(&listen_addr).await.map_err(|e| {
-        AppError::SshChannel(format!(
+    TcpListener::bind(&listen_addr).await.map_err(|e| {
+        AppError::Bind(format!(
             "Failed to bind {}: {}. Try another port or run as admin for port < 1024.",
             listen_addr, e
         ))
-    })?;
+    })
+}
 
-    info!(
-        channel = %config.name,
-        listen = %listen_addr,
-        "Local listener started, accepting connections"
-    );
+/// Accept local clients for the channel's whole lifetime. Each client is relayed through the
+/// current session; while reconnecting it waits up to `client_grace_secs` for the next one.
+async fn run_local_listener(
+    listener: TcpListener,
+    config: ChannelConfig,
+    mut pool: watch::Receiver<SessionPool>,
+    metrics: Arc<ChannelMetrics>,
+    cancel: CancellationToken,
+) {
+    let grace = Duration::from_secs(config.reconnection.client_grace_secs);
+    let picker = Arc::new(SessionPicker::new(config.balance));
+    let listen_addr = listener
+        .local_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_default();
+    info!(channel = %config.name, listen = %listen_addr, "Local listener started, accepting connections");
 
     loop {
         tokio::select! {
-            _ = cancel.cancelled() => {
-                info!(channel = %config.name, "Listener cancelled");
-                return Ok(());
+            _ = cancel.cancelled() => break,
+            // Sender dropped: the channel stopped or gave up
+            changed = pool.changed() => {
+                if changed.is_err() {
+                    break;
+                }
             }
             accept_result = listener.accept() => {
-                let (mut stream, peer_addr) = match accept_result {
+                let (stream, peer_addr) = match accept_result {
                     Ok(x) => x,
                     Err(e) => {
                         error!(channel = %config.name, error = ?e, "Accept failed");
                         continue;
                     }
                 };
-                let channel_name = config.name.clone();
-                let dest_host = dest_host.clone();
-                let dest_port = *dest_port;
-                match session.channel_open_direct_tcpip(
-                    &dest_host,
-                    dest_port as u32,
-                    "127.0.0.1",
-                    0u32,
-                ).await {
-                    Ok(channel) => {
-                        debug!(
-                            channel = %channel_name,
-                            peer = %peer_addr,
-                            dest = %format!("{}:{}", dest_host, dest_port),
-                            "Direct TCP/IP channel opened for connection"
-                        );
-                        let mut channel_stream = channel.into_stream();
-                        tokio::spawn(async move {
-                            if let Err(e) =
-                                tokio::io::copy_bidirectional(&mut stream, &mut channel_stream).await
-                            {
-                                debug!(channel = %channel_name, error = ?e, "Relay ended");
-                            }
-                        });
+                let config = config.clone();
+                let pool = pool.clone();
+                let picker = Arc::clone(&picker);
+                let metrics = Arc::clone(&metrics);
+                tokio::spawn(async move {
+                    let sessions = Sessions { pool, picker: &picker, grace };
+                    match &config.params {
+                        ChannelTypeParams::Dynamic { .. } => {
+                            serve_socks_client(stream, peer_addr, &config, sessions, &metrics).await
+                        }
+                        _ => serve_direct_client(stream, peer_addr, &config, sessions, &metrics).await,
                     }
-                    Err(e) => {
-                        error!(
-                            channel = %channel_name,
-                            error = ?e,
-                            "Failed to open direct-tcpip channel for new connection"
-                        );
+                });
+            }
+        }
+    }
+    info!(channel = %config.name, listen = %listen_addr, "Local listener closed");
+}
+
+/// What a local client needs to get a session: the pool, how to choose, how long to wait
+struct Sessions<'a> {
+    pool: watch::Receiver<SessionPool>,
+    picker: &'a SessionPicker,
+    grace: Duration,
+}
+
+impl Sessions<'_> {
+    /// Wait (up to the grace period) for a live session and open a direct-tcpip channel on it.
+    /// A channel that fails because the session just died is retried on another session.
+    /// The returned guard counts the client against the session until dropped.
+    async fn open_direct_tcpip(
+        &mut self,
+        dest_host: &str,
+        dest_port: u16,
+    ) -> Option<std::result::Result<(Channel<client::Msg>, RelayGuard), russh::Error>> {
+        let deadline = tokio::time::Instant::now() + self.grace;
+        loop {
+            let session = tokio::time::timeout_at(deadline, async {
+                loop {
+                    let live = self.picker.pick(&self.pool.borrow_and_update());
+                    if live.is_some() {
+                        return live;
                     }
+                    self.pool.changed().await.ok()?;
                 }
+            })
+            .await
+            .ok()
+            .flatten()?;
+
+            let guard = RelayGuard::new(&session.relays);
+            match session
+                .handle
+                .channel_open_direct_tcpip(dest_host, dest_port as u32, "127.0.0.1", 0u32)
+                .await
+            {
+                Err(_) if session.handle.is_closed() => continue,
+                result => return Some(result.map(|channel| (channel, guard))),
             }
         }
     }
 }
+
+/// Relay one local-forward client to `dest_host:dest_port` on the remote side
+async fn serve_direct_client(
+    mut stream: TcpStream,
+    peer_addr: SocketAddr,
+    config: &ChannelConfig,
+    mut sessions: Sessions<'_>,
+    metrics: &Arc<ChannelMetrics>,
+) {
+    let ChannelTypeParams::DirectTcpIp {
+        dest_host,
+        dest_port,
+        ..
+    } = &config.params
+    else {
+        return;
+    };
+
+    match sessions.open_direct_tcpip(dest_host, *dest_port).await {
+        Some(Ok((channel, _load))) => {
+            debug!(
+                channel = %config.name,
+                peer = %peer_addr,
+                dest = %format!("{}:{}", dest_host, dest_port),
+                "Direct TCP/IP channel opened for connection"
+            );
+            let relay = metrics.relay(
+                peer_addr.to_string(),
+                format!("{}:{}", dest_host, dest_port),
+            );
+            relay_stream(&config.name, "direct-tcpip", stream, channel, relay).await;
+        }
+        Some(Err(e)) => {
+            ChannelMetrics::inc(&metrics.channel_open_failures);
+            audit::record(AuditEntry::refused(
+                &config.name,
+                "direct-tcpip",
+                peer_addr.to_string(),
+                format!("{}:{}", dest_host, dest_port),
+                audit::Outcome::OpenFailed,
+                Some(e.to_string()),
+            ));
+            error!(
+                channel = %config.name,
+                error = ?e,
+                "Failed to open direct-tcpip channel for new connection"
+            );
+            // Close at once so the client (and `test`) sees the refusal
+            let _ = stream.shutdown().await;
+        }
+        None => {
+            audit::record(AuditEntry::refused(
+                &config.name,
+                "direct-tcpip",
+                peer_addr.to_string(),
+                format!("{}:{}", dest_host, dest_port),
+                audit::Outcome::NoSession,
+                None,
+            ));
+            warn!(
+                channel = %config.name,
+                peer = %peer_addr,
+                "No SSH session within {}s, closing client connection",
+                sessions.grace.as_secs()
+            );
+            let _ = stream.shutdown().await;
+        }
+    }
+}
+
+/// Copy between the local end of a relay and its SSH channel until both sides are done,
+/// accounting the traffic to `relay` and writing the outcome to the audit log
+async fn relay_stream(
+    channel_name: &str,
+    channel_type: &'static str,
+    local: TcpStream,
+    channel: Channel<client::Msg>,
+    relay: ActiveRelay,
+) -> RelayRecord {
+    events::emit(channel_name, EventKind::RelayOpened(relay.record()));
+    let mut channel_stream = channel.into_stream();
+    let result = {
+        let mut local = relay.meter(local);
+        tokio::io::copy_bidirectional(&mut local, &mut channel_stream).await
+    };
+    let record = relay.finish(&result);
+    debug!(
+        channel = %channel_name,
+        peer = %record.peer,
+        dest = %record.destination,
+        sent = record.bytes_sent,
+        received = record.bytes_received,
+        duration_ms = record.duration_ms,
+        reason = record.close_reason.as_deref().unwrap_or_default(),
+        "Relay ended"
+    );
+    audit::record(AuditEntry::relay(channel_name, channel_type, &record));
+    events::emit(channel_name, EventKind::RelayClosed(record.clone()));
+    record
+}
+
+/// Serve one SOCKS5 client (ssh -D style): each CONNECT request opens a direct-tcpip channel
+async fn serve_socks_client(
+    mut stream: TcpStream,
+    peer_addr: SocketAddr,
+    config: &ChannelConfig,
+    mut sessions: Sessions<'_>,
+    metrics: &Arc<ChannelMetrics>,
+) {
+    let (dest_host, dest_port) = match socks::accept_connect(&mut stream).await {
+        Ok(target) => target,
+        Err(e) => {
+            debug!(channel = %config.name, peer = %peer_addr, error = ?e, "SOCKS handshake failed");
+            return;
+        }
+    };
+
+    match sessions.open_direct_tcpip(&dest_host, dest_port).await {
+        Some(Ok((channel, _load))) => {
+            if socks::send_reply(&mut stream, socks::REPLY_SUCCEEDED)
+                .await
+                .is_err()
+            {
+                return;
+            }
+            debug!(
+                channel = %config.name,
+                peer = %peer_addr,
+                dest = %format!("{}:{}", dest_host, dest_port),
+                "Direct TCP/IP channel opened for SOCKS connection"
+            );
+            let relay = metrics.relay(
+                peer_addr.to_string(),
+                format!("{}:{}", dest_host, dest_port),
+            );
+            relay_stream(&config.name, "dynamic", stream, channel, relay).await;
+        }
+        Some(Err(e)) => {
+            ChannelMetrics::inc(&metrics.channel_open_failures);
+            audit::record(AuditEntry::refused(
+                &config.name,
+                "dynamic",
+                peer_addr.to_string(),
+                format!("{}:{}", dest_host, dest_port),
+                audit::Outcome::OpenFailed,
+                Some(e.to_string()),
+            ));
+            let _ = socks::send_reply(&mut stream, socks::REPLY_GENERAL_FAILURE).await;
+            error!(
+                channel = %config.name,
+                dest = %format!("{}:{}", dest_host, dest_port),
+                error = ?e,
+                "Failed to open direct-tcpip channel for SOCKS connection"
+            );
+        }
+        None => {
+            let _ = socks::send_reply(&mut stream, socks::REPLY_GENERAL_FAILURE).await;
+            audit::record(AuditEntry::refused(
+                &config.name,
+                "dynamic",
+                peer_addr.to_string(),
+                format!("{}:{}", dest_host, dest_port),
+                audit::Outcome::NoSession,
+                None,
+            ));
+            warn!(
+                channel = %config.name,
+                peer = %peer_addr,
+                "No SSH session within {}s, closing SOCKS connection",
+                sessions.grace.as_secs()
+            );
+        }
+    }
+}
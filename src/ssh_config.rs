@@ -1,6 +1,9 @@
 use crate::error::{AppError, Result};
-use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Maximum nesting depth for `Include` directives (same limit as OpenSSH)
+const MAX_INCLUDE_DEPTH: usize = 16;
 
 /// SSH config entry parsed from ~/.ssh/config
 #[derive(Debug, Clone)]
@@ -13,28 +16,120 @@ pub struct SshConfigEntry {
     pub port: Option<u16>,
     /// Username
     pub user: Option<String>,
-    /// Identity file path
+    /// Identity file path (first one obtained)
     pub identity_file: Option<PathBuf>,
+    /// ProxyJump specification (raw value, e.g. "bastion" or "user@jump:2222")
+    pub proxy_jump: Option<String>,
+    /// LocalForward directives (ssh -L)
+    pub local_forwards: Vec<ForwardSpec>,
+    /// RemoteForward directives (ssh -R)
+    pub remote_forwards: Vec<ForwardSpec>,
+    /// DynamicForward directives (ssh -D)
+    pub dynamic_forwards: Vec<DynamicForwardSpec>,
+    /// ServerAliveInterval in seconds
+    pub server_alive_interval: Option<u64>,
+    /// ServerAliveCountMax
+    pub server_alive_count_max: Option<usize>,
+    /// IdentitiesOnly: use only the configured IdentityFile, never default keys
+    pub identities_only: Option<bool>,
+}
+
+/// A `LocalForward` / `RemoteForward` directive: `[bind_address:]port host:hostport`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardSpec {
+    /// Bind address (None = default, i.e. loopback)
+    pub bind_address: Option<String>,
+    /// Port to listen on
+    pub bind_port: u16,
+    /// Host to connect to on the other side
+    pub connect_host: String,
+    /// Port to connect to on the other side
+    pub connect_port: u16,
+}
+
+/// A `DynamicForward` directive: `[bind_address:]port`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicForwardSpec {
+    /// Bind address (None = default, i.e. loopback)
+    pub bind_address: Option<String>,
+    /// Port to listen on
+    pub bind_port: u16,
 }
 
-/// Default values from Host "*" entry
+/// Parsed SSH config document, evaluated per host alias like OpenSSH does
 #[derive(Debug, Clone, Default)]
-struct SshConfigDefaults {
+pub struct SshConfig {
+    sections: Vec<Section>,
+}
+
+/// A block of directives guarded by a `Host` or `Match` line
+#[derive(Debug, Clone)]
+struct Section {
+    condition: Condition,
+    directives: Vec<Directive>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    /// Directives before the first Host/Match line
+    All,
+    /// `Host pattern...`
+    Host(Vec<Pattern>),
+    /// `Match criterion...`
+    Match(Vec<MatchCriterion>),
+}
+
+#[derive(Debug, Clone)]
+enum Directive {
+    /// A keyword (lowercased) with its arguments
+    Option { keyword: String, args: Vec<String> },
+    /// Contents of the files pulled in by an `Include` line
+    Include(Vec<Section>),
+}
+
+/// A single (possibly negated) wildcard pattern
+#[derive(Debug, Clone)]
+struct Pattern {
+    negated: bool,
+    pattern: String,
+}
+
+#[derive(Debug, Clone)]
+struct MatchCriterion {
+    negated: bool,
+    kind: MatchKind,
+}
+
+#[derive(Debug, Clone)]
+enum MatchKind {
+    All,
+    Host(Vec<Pattern>),
+    OriginalHost(Vec<Pattern>),
+    User(Vec<Pattern>),
+    LocalUser(Vec<Pattern>),
+    /// `exec`, `canonical`, `final`, `localnetwork`, ... (never match)
+    Unsupported(String),
+}
+
+/// Values obtained so far while evaluating the config for one alias
+#[derive(Debug, Default)]
+struct Resolved {
+    hostname: Option<String>,
     port: Option<u16>,
     user: Option<String>,
-    identity_file: Option<PathBuf>,
+    identity_files: Vec<String>,
+    proxy_jump: Option<String>,
+    local_forwards: Vec<ForwardSpec>,
+    remote_forwards: Vec<ForwardSpec>,
+    dynamic_forwards: Vec<DynamicForwardSpec>,
+    server_alive_interval: Option<u64>,
+    server_alive_count_max: Option<usize>,
+    identities_only: Option<bool>,
 }
 
 /// Parse SSH config file
 pub fn parse_ssh_config(path: impl AsRef<Path>) -> Result<Vec<SshConfigEntry>> {
-    let path = expand_tilde(path.as_ref())?;
-
-    let content = std::fs::read_to_string(&path)
-        .map_err(|e| AppError::Config(format!("Failed to read SSH config file: {}", e)))?;
-
-    let entries = parse_ssh_config_content(&content)?;
-
-    Ok(entries)
+    Ok(SshConfig::from_file(path)?.entries())
 }
 
 /// Get default SSH config path (~/.ssh/config)
@@ -48,8 +143,116 @@ pub fn default_ssh_config_path() -> PathBuf {
     }
 }
 
+impl SshConfigEntry {
+    /// Jump host the alias is reached through, if any (`ProxyJump none` means none)
+    pub fn jump_host(&self) -> Option<&str> {
+        self.proxy_jump
+            .as_deref()
+            .filter(|jump| !jump.eq_ignore_ascii_case("none"))
+    }
+}
+
+impl SshConfig {
+    /// Load an SSH config file. Relative `Include` paths are resolved against the file's directory.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = expand_tilde(path.as_ref())?;
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| AppError::Config(format!("Failed to read SSH config file: {}", e)))?;
+
+        let base_dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(default_include_dir);
+
+        Ok(Self {
+            sections: parse_sections(&content, &base_dir, 0)?,
+        })
+    }
+
+    /// Parse SSH config content. Relative `Include` paths are resolved against `~/.ssh`.
+    #[cfg(test)]
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(Self {
+            sections: parse_sections(content, &default_include_dir(), 0)?,
+        })
+    }
+
+    /// Host aliases declared in `Host` lines (non-wildcard, non-negated), in order of appearance
+    pub fn host_aliases(&self) -> Vec<String> {
+        let mut aliases = Vec::new();
+        collect_aliases(&self.sections, &mut aliases);
+        aliases
+    }
+
+    /// Evaluate the config for `alias`: every matching block contributes, first obtained value wins
+    pub fn resolve(&self, alias: &str) -> SshConfigEntry {
+        let local_user = local_username();
+        let mut resolved = Resolved::default();
+        apply_sections(&self.sections, alias, &local_user, &mut resolved);
+
+        let hostname = resolved.hostname.map(|h| {
+            let tokens = Tokens {
+                hostname: alias,
+                alias,
+                port: resolved.port.unwrap_or(22),
+                remote_user: resolved.user.as_deref().unwrap_or(&local_user),
+                local_user: &local_user,
+            };
+            expand_tokens(&h, &tokens)
+        });
+
+        let identity_file = resolved.identity_files.first().and_then(|p| {
+            let tokens = Tokens {
+                hostname: hostname.as_deref().unwrap_or(alias),
+                alias,
+                port: resolved.port.unwrap_or(22),
+                remote_user: resolved.user.as_deref().unwrap_or(&local_user),
+                local_user: &local_user,
+            };
+            expand_tilde_in_path(&expand_tokens(p, &tokens))
+        });
+
+        SshConfigEntry {
+            host: alias.to_string(),
+            hostname,
+            port: resolved.port,
+            user: resolved.user,
+            identity_file,
+            proxy_jump: resolved.proxy_jump,
+            local_forwards: resolved.local_forwards,
+            remote_forwards: resolved.remote_forwards,
+            dynamic_forwards: resolved.dynamic_forwards,
+            server_alive_interval: resolved.server_alive_interval,
+            server_alive_count_max: resolved.server_alive_count_max,
+            identities_only: resolved.identities_only,
+        }
+    }
+
+    /// Resolve every declared alias, skipping those without a HostName (patterns or incomplete entries)
+    pub fn entries(&self) -> Vec<SshConfigEntry> {
+        self.host_aliases()
+            .iter()
+            .map(|alias| self.resolve(alias))
+            .filter(|entry| entry.hostname.is_some())
+            .collect()
+    }
+}
+
+/// Check an alias against a comma-separated pattern list (e.g. "prod-*,!prod-legacy")
+pub fn alias_matches(alias: &str, patterns: &str) -> bool {
+    match_pattern_list(alias, &parse_pattern_list(patterns), true)
+}
+
+/// Directory relative `Include` paths are resolved against when no config file is known
+fn default_include_dir() -> PathBuf {
+    dirs::home_dir()
+        .map(|home| home.join(".ssh"))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
 /// Expand tilde (~) in path
-fn expand_tilde(path: &Path) -> Result<PathBuf> {
+pub fn expand_tilde(path: &Path) -> Result<PathBuf> {
     let path_str = path.to_string_lossy();
 
     if path_str == "~" {
@@ -75,14 +278,19 @@ fn expand_tilde(path: &Path) -> Result<PathBuf> {
 }
 
 /// Parse SSH config content
+#[cfg(test)]
 fn parse_ssh_config_content(content: &str) -> Result<Vec<SshConfigEntry>> {
-    let mut entries = Vec::new();
-    let mut current_host: Option<String> = None;
-    let mut current_config: HashMap<String, String> = HashMap::new();
-    let mut defaults = SshConfigDefaults::default();
-    let mut is_default_host = false;
+    Ok(SshConfig::parse(content)?.entries())
+}
+
+/// Parse config text into sections, following `Include` directives
+fn parse_sections(content: &str, base_dir: &Path, depth: usize) -> Result<Vec<Section>> {
+    let mut sections = vec![Section {
+        condition: Condition::All,
+        directives: Vec::new(),
+    }];
 
-    for line in content.lines() {
+    for (line_no, line) in content.lines().enumerate() {
         let line = line.trim();
 
         // Skip empty lines and comments
@@ -90,115 +298,479 @@ fn parse_ssh_config_content(content: &str) -> Result<Vec<SshConfigEntry>> {
             continue;
         }
 
-        // Handle Host directive (starts a new entry)
-        if line.starts_with("Host ") {
-            // Save previous entry if exists
-            if let Some(host) = current_host.take() {
-                if !is_default_host {
-                    if let Some(entry) = build_entry(&host, &current_config, &defaults) {
-                        entries.push(entry);
-                    }
-                } else {
-                    // This was Host "*", save as defaults
-                    defaults = extract_defaults(&current_config);
+        let Some((keyword, args)) = split_directive(line) else {
+            continue;
+        };
+        let keyword = keyword.to_lowercase();
+
+        match keyword.as_str() {
+            "host" => {
+                let patterns = args.iter().map(|a| Pattern::parse(a)).collect();
+                sections.push(Section {
+                    condition: Condition::Host(patterns),
+                    directives: Vec::new(),
+                });
+            }
+            "match" => {
+                let criteria = parse_match_criteria(&args).map_err(|e| {
+                    AppError::Config(format!("SSH config line {}: {}", line_no + 1, e))
+                })?;
+                sections.push(Section {
+                    condition: Condition::Match(criteria),
+                    directives: Vec::new(),
+                });
+            }
+            "include" => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(AppError::Config(format!(
+                        "SSH config Include nested too deeply (max {})",
+                        MAX_INCLUDE_DEPTH
+                    )));
+                }
+                let included = parse_includes(&args, base_dir, depth)?;
+                if let Some(current) = sections.last_mut() {
+                    current.directives.push(Directive::Include(included));
                 }
             }
-            current_config.clear();
-            is_default_host = false;
-
-            // Extract host name(s) - can be space-separated or wildcards
-            let hosts = line[4..].trim();
-            // For simplicity, we'll use the first host name
-            // In real SSH config, multiple hosts can share the same config
-            let host = hosts.split_whitespace().next().unwrap_or("").to_string();
-
-            if host == "*" {
-                // This is the default host entry
-                is_default_host = true;
-                current_host = Some(host);
-            } else if !host.is_empty() {
-                current_host = Some(host);
+            _ => {
+                if args.is_empty() {
+                    continue;
+                }
+                if let Some(current) = sections.last_mut() {
+                    current.directives.push(Directive::Option { keyword, args });
+                }
             }
-        } else if current_host.is_some() {
-            // Parse other directives
-            if let Some((key, value)) = parse_directive(line) {
-                current_config.insert(key.to_lowercase(), value);
+        }
+    }
+
+    Ok(sections)
+}
+
+/// Read and parse every file matched by the `Include` arguments (globs allowed)
+fn parse_includes(args: &[String], base_dir: &Path, depth: usize) -> Result<Vec<Section>> {
+    let mut sections = Vec::new();
+
+    for arg in args {
+        let path = match arg.strip_prefix("~/") {
+            Some(_) => expand_tilde(Path::new(arg))?,
+            None if Path::new(arg).is_absolute() => PathBuf::from(arg),
+            None => base_dir.join(arg),
+        };
+
+        let pattern = path.to_string_lossy().to_string();
+        let mut matches: Vec<PathBuf> = glob::glob(&pattern)
+            .map_err(|e| AppError::Config(format!("Invalid Include pattern '{}': {}", arg, e)))?
+            .filter_map(|p| p.ok())
+            .collect();
+        matches.sort();
+
+        if matches.is_empty() {
+            debug!(pattern = %pattern, "SSH config Include matched no files");
+        }
+
+        for file in matches {
+            let content = std::fs::read_to_string(&file).map_err(|e| {
+                AppError::Config(format!(
+                    "Failed to read included SSH config '{}': {}",
+                    file.display(),
+                    e
+                ))
+            })?;
+            sections.extend(parse_sections(&content, base_dir, depth + 1)?);
+        }
+    }
+
+    Ok(sections)
+}
+
+/// Split a line into keyword and arguments. Supports `Key value`, `Key=value` and double quotes.
+fn split_directive(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim_start();
+    let key_end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = &line[..key_end];
+    if keyword.is_empty() {
+        return None;
+    }
+
+    let mut rest = line[key_end..].trim_start();
+    if let Some(stripped) = rest.strip_prefix('=') {
+        rest = stripped.trim_start();
+    }
+
+    Some((keyword.to_string(), split_args(rest)))
+}
+
+/// Split arguments on whitespace, honoring double-quoted strings
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+
+    for c in s.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    args.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
             }
         }
     }
+    if has_token {
+        args.push(current);
+    }
 
-    // Save last entry
-    if let Some(host) = current_host {
-        if !is_default_host {
-            if let Some(entry) = build_entry(&host, &current_config, &defaults) {
-                entries.push(entry);
+    args
+}
+
+impl Pattern {
+    fn parse(s: &str) -> Self {
+        match s.strip_prefix('!') {
+            Some(rest) => Pattern {
+                negated: true,
+                pattern: rest.to_string(),
+            },
+            None => Pattern {
+                negated: false,
+                pattern: s.to_string(),
+            },
+        }
+    }
+
+    fn is_wildcard(&self) -> bool {
+        self.pattern.contains(['*', '?'])
+    }
+}
+
+/// Parse a comma-separated pattern list (as used by `Match host a,b,!c`)
+fn parse_pattern_list(s: &str) -> Vec<Pattern> {
+    s.split(',')
+        .filter(|p| !p.is_empty())
+        .map(Pattern::parse)
+        .collect()
+}
+
+/// Parse `Match` arguments into criteria
+fn parse_match_criteria(args: &[String]) -> std::result::Result<Vec<MatchCriterion>, String> {
+    let mut criteria = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let (negated, name) = match arg.strip_prefix('!') {
+            Some(rest) => (true, rest.to_lowercase()),
+            None => (false, arg.to_lowercase()),
+        };
+
+        let kind = match name.as_str() {
+            "all" => MatchKind::All,
+            "canonical" | "final" => MatchKind::Unsupported(name),
+            "host" | "originalhost" | "user" | "localuser" | "exec" | "localnetwork" | "tagged" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Match '{}' requires an argument", name))?;
+                let patterns = parse_pattern_list(value);
+                match name.as_str() {
+                    "host" => MatchKind::Host(patterns),
+                    "originalhost" => MatchKind::OriginalHost(patterns),
+                    "user" => MatchKind::User(patterns),
+                    "localuser" => MatchKind::LocalUser(patterns),
+                    _ => MatchKind::Unsupported(name),
+                }
+            }
+            other => return Err(format!("unsupported Match criterion '{}'", other)),
+        };
+
+        criteria.push(MatchCriterion { negated, kind });
+    }
+
+    if criteria.is_empty() {
+        return Err("Match requires at least one criterion".to_string());
+    }
+
+    Ok(criteria)
+}
+
+/// Collect non-wildcard, non-negated `Host` patterns, deduplicated, in order of appearance
+fn collect_aliases(sections: &[Section], aliases: &mut Vec<String>) {
+    for section in sections {
+        if let Condition::Host(patterns) = &section.condition {
+            for pattern in patterns {
+                if !pattern.negated && !pattern.is_wildcard() && !aliases.contains(&pattern.pattern)
+                {
+                    aliases.push(pattern.pattern.clone());
+                }
+            }
+        }
+        for directive in &section.directives {
+            if let Directive::Include(included) = directive {
+                collect_aliases(included, aliases);
             }
-        } else {
-            // This was Host "*", save as defaults (for potential future use)
-            // Note: This won't affect already processed entries, which matches SSH config behavior
-            let _ = extract_defaults(&current_config);
         }
     }
+}
 
-    Ok(entries)
+/// Apply every matching section in order
+fn apply_sections(sections: &[Section], alias: &str, local_user: &str, resolved: &mut Resolved) {
+    for section in sections {
+        if !section_matches(&section.condition, alias, local_user, resolved) {
+            continue;
+        }
+        for directive in &section.directives {
+            match directive {
+                Directive::Option { keyword, args } => apply_option(keyword, args, resolved),
+                Directive::Include(included) => {
+                    apply_sections(included, alias, local_user, resolved)
+                }
+            }
+        }
+    }
 }
 
-/// Extract default values from Host "*" config
-fn extract_defaults(config: &HashMap<String, String>) -> SshConfigDefaults {
-    SshConfigDefaults {
-        port: config.get("port").and_then(|p| p.parse::<u16>().ok()),
-        user: config.get("user").cloned(),
-        identity_file: config
-            .get("identityfile")
-            .and_then(|p| expand_tilde_in_path(p)),
+fn section_matches(
+    condition: &Condition,
+    alias: &str,
+    local_user: &str,
+    resolved: &Resolved,
+) -> bool {
+    match condition {
+        Condition::All => true,
+        Condition::Host(patterns) => match_pattern_list(alias, patterns, true),
+        Condition::Match(criteria) => criteria.iter().all(|criterion| {
+            let matched = match &criterion.kind {
+                MatchKind::All => true,
+                MatchKind::Host(patterns) => {
+                    let host = resolved.hostname.as_deref().unwrap_or(alias);
+                    let host = host.replace("%h", alias);
+                    match_pattern_list(&host, patterns, true)
+                }
+                MatchKind::OriginalHost(patterns) => match_pattern_list(alias, patterns, true),
+                MatchKind::User(patterns) => {
+                    let user = resolved.user.as_deref().unwrap_or(local_user);
+                    match_pattern_list(user, patterns, false)
+                }
+                MatchKind::LocalUser(patterns) => match_pattern_list(local_user, patterns, false),
+                MatchKind::Unsupported(name) => {
+                    debug!(criterion = %name, "Unsupported Match criterion, block skipped");
+                    return false;
+                }
+            };
+            matched != criterion.negated
+        }),
     }
 }
 
-/// Parse a directive line (e.g., "HostName example.com")
-fn parse_directive(line: &str) -> Option<(&str, String)> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() >= 2 {
-        let key = parts[0];
-        let value = parts[1..].join(" "); // Handle values with spaces
-        Some((key, value))
+/// OpenSSH pattern-list semantics: any negated match rejects, otherwise any positive match accepts
+fn match_pattern_list(value: &str, patterns: &[Pattern], ignore_case: bool) -> bool {
+    let value = if ignore_case {
+        value.to_lowercase()
     } else {
-        None
-    }
-}
-
-/// Build SshConfigEntry from host name and config map, applying defaults
-fn build_entry(
-    host: &str,
-    config: &HashMap<String, String>,
-    defaults: &SshConfigDefaults,
-) -> Option<SshConfigEntry> {
-    // Skip entries without HostName (they might be patterns or incomplete)
-    let hostname = config.get("hostname")?.clone();
-
-    let port = config
-        .get("port")
-        .and_then(|p| p.parse::<u16>().ok())
-        .or(defaults.port);
-
-    let user = config
-        .get("user")
-        .cloned()
-        .or_else(|| defaults.user.clone());
-
-    let identity_file = config
-        .get("identityfile")
-        .and_then(|p| expand_tilde_in_path(p))
-        .or_else(|| defaults.identity_file.clone());
-
-    Some(SshConfigEntry {
-        host: host.to_string(),
-        hostname: Some(hostname),
-        port,
-        user,
-        identity_file,
+        value.to_string()
+    };
+    let mut found = false;
+
+    for pattern in patterns {
+        let p = if ignore_case {
+            pattern.pattern.to_lowercase()
+        } else {
+            pattern.pattern.clone()
+        };
+        if wildcard_match(&p, &value) {
+            if pattern.negated {
+                return false;
+            }
+            found = true;
+        }
+    }
+
+    found
+}
+
+/// Match `*` (any sequence) and `?` (any single char)
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let v: Vec<char> = value.chars().collect();
+    let (mut pi, mut vi) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while vi < v.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == v[vi]) {
+            pi += 1;
+            vi += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, vi));
+            pi += 1;
+        } else if let Some((sp, sv)) = star {
+            pi = sp + 1;
+            vi = sv + 1;
+            star = Some((sp, sv + 1));
+        } else {
+            return false;
+        }
+    }
+
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+/// Apply a single directive. Scalar options keep the first obtained value; list options accumulate.
+fn apply_option(keyword: &str, args: &[String], resolved: &mut Resolved) {
+    let value = &args[0];
+
+    match keyword {
+        "hostname" => set_first(&mut resolved.hostname, Some(value.clone())),
+        "port" => set_first(&mut resolved.port, parse_or_warn(keyword, value)),
+        "user" => set_first(&mut resolved.user, Some(value.clone())),
+        "identityfile" => resolved.identity_files.push(value.clone()),
+        "proxyjump" => set_first(&mut resolved.proxy_jump, Some(args.join(","))),
+        "serveraliveinterval" => set_first(
+            &mut resolved.server_alive_interval,
+            parse_or_warn(keyword, value),
+        ),
+        "serveralivecountmax" => set_first(
+            &mut resolved.server_alive_count_max,
+            parse_or_warn(keyword, value),
+        ),
+        "identitiesonly" => set_first(&mut resolved.identities_only, parse_yes_no(value)),
+        "localforward" => match parse_forward(args) {
+            Some(spec) => resolved.local_forwards.push(spec),
+            None => warn!(value = %args.join(" "), "Ignoring unsupported LocalForward"),
+        },
+        "remoteforward" => match parse_forward(args) {
+            Some(spec) => resolved.remote_forwards.push(spec),
+            None => warn!(value = %args.join(" "), "Ignoring unsupported RemoteForward"),
+        },
+        "dynamicforward" => match parse_listen_spec(value) {
+            Some((bind_address, bind_port)) => resolved.dynamic_forwards.push(DynamicForwardSpec {
+                bind_address,
+                bind_port,
+            }),
+            None => warn!(value = %value, "Ignoring invalid DynamicForward"),
+        },
+        _ => {}
+    }
+}
+
+fn set_first<T>(slot: &mut Option<T>, value: Option<T>) {
+    if slot.is_none() {
+        *slot = value;
+    }
+}
+
+fn parse_or_warn<T: std::str::FromStr>(keyword: &str, value: &str) -> Option<T> {
+    let parsed = value.parse::<T>().ok();
+    if parsed.is_none() {
+        warn!(keyword = %keyword, value = %value, "Ignoring invalid SSH config value");
+    }
+    parsed
+}
+
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "yes" | "true" => Some(true),
+        "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse `[bind_address:]port host:hostport` (also `host/hostport` and `[ipv6]:port`)
+fn parse_forward(args: &[String]) -> Option<ForwardSpec> {
+    if args.len() < 2 {
+        return None;
+    }
+    let (bind_address, bind_port) = parse_listen_spec(&args[0])?;
+    let (connect_host, connect_port) = split_host_port(&args[1])?;
+    Some(ForwardSpec {
+        bind_address,
+        bind_port,
+        connect_host,
+        connect_port,
     })
 }
 
+/// Parse `[bind_address:]port`
+fn parse_listen_spec(s: &str) -> Option<(Option<String>, u16)> {
+    if let Ok(port) = s.parse::<u16>() {
+        return Some((None, port));
+    }
+    let (host, port) = split_host_port(s)?;
+    Some((Some(host), port))
+}
+
+/// Split `host:port`, `host/port` or `[ipv6]:port`
+fn split_host_port(s: &str) -> Option<(String, u16)> {
+    if let Some(rest) = s.strip_prefix('[') {
+        let (host, port) = rest.split_once(']')?;
+        let port = port.strip_prefix(':').or_else(|| port.strip_prefix('/'))?;
+        return Some((host.to_string(), port.parse().ok()?));
+    }
+    let (host, port) = s.rsplit_once(':').or_else(|| s.rsplit_once('/'))?;
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+/// Values available for `%` token expansion
+struct Tokens<'a> {
+    hostname: &'a str,
+    alias: &'a str,
+    port: u16,
+    remote_user: &'a str,
+    local_user: &'a str,
+}
+
+/// Expand `%h`, `%n`, `%p`, `%r`, `%u`, `%d` and `%%`. Unknown tokens are kept verbatim.
+fn expand_tokens(s: &str, tokens: &Tokens) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some('h') => out.push_str(tokens.hostname),
+            Some('n') => out.push_str(tokens.alias),
+            Some('p') => out.push_str(&tokens.port.to_string()),
+            Some('r') => out.push_str(tokens.remote_user),
+            Some('u') => out.push_str(tokens.local_user),
+            Some('d') => match dirs::home_dir() {
+                Some(home) => out.push_str(&home.to_string_lossy()),
+                None => out.push_str("%d"),
+            },
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+
+    out
+}
+
+/// Name of the local user running the process
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
 /// Expand tilde in a path string
 fn expand_tilde_in_path(path: &str) -> Option<PathBuf> {
     if let Some(rest) = path.strip_prefix("~/") {
@@ -247,11 +819,6 @@ Host myserver2
     #[test]
     fn test_default_values_from_wildcard_host() {
         let content = r#"
-Host *
-    Port 2222
-    User defaultuser
-    IdentityFile ~/.ssh/default_key
-
 Host myserver
     HostName example.com
 
@@ -259,6 +826,11 @@ Host myserver2
     HostName example2.com
     Port 22
     User customuser
+
+Host *
+    Port 2222
+    User defaultuser
+    IdentityFile ~/.ssh/default_key
 "#;
 
         let entries = parse_ssh_config_content(content).unwrap();
@@ -278,6 +850,78 @@ Host myserver2
         assert_eq!(entries[1].user, Some("customuser".to_string())); // Overridden
     }
 
+    #[test]
+    fn test_leading_wildcard_host_wins_over_later_blocks() {
+        // Fixture as written for the old parser, which let later Host blocks override Host *.
+        // With OpenSSH semantics the leading Host * supplies Port and User to every alias.
+        let content = r#"
+Host *
+    Port 2222
+    User defaultuser
+    IdentityFile ~/.ssh/default_key
+
+Host myserver
+    HostName example.com
+
+Host myserver2
+    HostName example2.com
+    Port 22
+    User customuser
+"#;
+
+        let entries = parse_ssh_config_content(content).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].host, "myserver");
+        assert_eq!(entries[0].port, Some(2222));
+        assert_eq!(entries[0].user, Some("defaultuser".to_string()));
+        assert!(entries[0].identity_file.is_some());
+
+        assert_eq!(entries[1].host, "myserver2");
+        assert_eq!(entries[1].hostname, Some("example2.com".to_string()));
+        assert_eq!(entries[1].port, Some(2222)); // Host * came first
+        assert_eq!(entries[1].user, Some("defaultuser".to_string())); // Host * came first
+    }
+
+    #[test]
+    fn test_first_obtained_value_wins() {
+        // Like OpenSSH, a leading Host * takes precedence over later, more specific blocks
+        let content = r#"
+Host *
+    Port 2222
+
+Host myserver
+    HostName example.com
+    Port 22
+    User me
+"#;
+
+        let entries = parse_ssh_config_content(content).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].port, Some(2222));
+        assert_eq!(entries[0].user, Some("me".to_string()));
+    }
+
+    #[test]
+    fn test_identities_only_first_value_wins() {
+        let content = r#"
+Host strict
+    HostName strict.example.com
+    IdentitiesOnly yes
+
+Host loose
+    HostName loose.example.com
+    IdentitiesOnly no
+
+Host *
+    IdentitiesOnly yes
+"#;
+
+        let entries = parse_ssh_config_content(content).unwrap();
+        assert_eq!(entries[0].identities_only, Some(true));
+        assert_eq!(entries[1].identities_only, Some(false));
+    }
+
     #[test]
     fn test_wildcard_host_not_included_in_entries() {
         let content = r#"
@@ -294,4 +938,176 @@ Host myserver
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0].host, "myserver");
     }
+
+    #[test]
+    fn test_multiple_patterns_and_negation() {
+        let content = r#"
+Host web1 web2
+    HostName %h.example.com
+
+Host *.internal !secret.internal
+    User internal-user
+
+Host db.internal secret.internal
+    HostName 10.0.0.5
+"#;
+
+        let config = SshConfig::parse(content).unwrap();
+        assert_eq!(
+            config.host_aliases(),
+            vec!["web1", "web2", "db.internal", "secret.internal"]
+        );
+
+        let web2 = config.resolve("web2");
+        assert_eq!(web2.hostname.as_deref(), Some("web2.example.com"));
+
+        let db = config.resolve("db.internal");
+        assert_eq!(db.user.as_deref(), Some("internal-user"));
+
+        let secret = config.resolve("secret.internal");
+        assert_eq!(secret.user, None);
+    }
+
+    #[test]
+    fn test_match_blocks() {
+        let content = r#"
+Host bastion
+    HostName bastion.example.com
+    User ops
+
+Host app
+    HostName app.example.com
+
+Match host *.example.com user ops
+    Port 2200
+
+Match originalhost app
+    User deploy
+
+Match exec "true"
+    Port 9999
+
+Match all
+    ServerAliveInterval 30
+"#;
+
+        let config = SshConfig::parse(content).unwrap();
+
+        let bastion = config.resolve("bastion");
+        assert_eq!(bastion.port, Some(2200));
+        assert_eq!(bastion.server_alive_interval, Some(30));
+
+        // User is set by the originalhost block, after the host+user block was evaluated
+        let app = config.resolve("app");
+        assert_eq!(app.port, None);
+        assert_eq!(app.user.as_deref(), Some("deploy"));
+        assert_eq!(app.server_alive_interval, Some(30));
+    }
+
+    #[test]
+    fn test_token_expansion() {
+        let content = r#"
+Host myserver
+    HostName example.com
+    User alice
+    Port 2022
+    IdentityFile /keys/%r@%h-%p-%n%%
+"#;
+
+        let entry = SshConfig::parse(content).unwrap().resolve("myserver");
+        assert_eq!(
+            entry.identity_file,
+            Some(PathBuf::from("/keys/alice@example.com-2022-myserver%"))
+        );
+    }
+
+    #[test]
+    fn test_forwards_and_options() {
+        let content = r#"
+Host myserver
+    HostName example.com
+    ProxyJump jump1,jump2
+    LocalForward 8080 localhost:80
+    LocalForward 0.0.0.0:5432 db.internal:5432
+    RemoteForward 9000 127.0.0.1:3000
+    DynamicForward 1080
+    IdentitiesOnly yes
+    ServerAliveInterval=15
+    ServerAliveCountMax 5
+    IdentityFile ~/.ssh/first
+    IdentityFile ~/.ssh/second
+"#;
+
+        let entry = SshConfig::parse(content).unwrap().resolve("myserver");
+        assert_eq!(entry.proxy_jump.as_deref(), Some("jump1,jump2"));
+        assert_eq!(
+            entry.local_forwards,
+            vec![
+                ForwardSpec {
+                    bind_address: None,
+                    bind_port: 8080,
+                    connect_host: "localhost".to_string(),
+                    connect_port: 80,
+                },
+                ForwardSpec {
+                    bind_address: Some("0.0.0.0".to_string()),
+                    bind_port: 5432,
+                    connect_host: "db.internal".to_string(),
+                    connect_port: 5432,
+                },
+            ]
+        );
+        assert_eq!(entry.remote_forwards.len(), 1);
+        assert_eq!(entry.remote_forwards[0].bind_port, 9000);
+        assert_eq!(entry.remote_forwards[0].connect_port, 3000);
+        assert_eq!(
+            entry.dynamic_forwards,
+            vec![DynamicForwardSpec {
+                bind_address: None,
+                bind_port: 1080
+            }]
+        );
+        assert_eq!(entry.identities_only, Some(true));
+        assert_eq!(entry.server_alive_interval, Some(15));
+        assert_eq!(entry.server_alive_count_max, Some(5));
+        assert!(entry.identity_file.unwrap().ends_with(".ssh/first"));
+    }
+
+    #[test]
+    fn test_alias_matches() {
+        assert!(alias_matches("prod-web", "prod-*"));
+        assert!(alias_matches("dev", "prod-*,dev"));
+        assert!(!alias_matches("prod-legacy", "prod-*,!prod-legacy"));
+        assert!(!alias_matches("staging", "prod-*"));
+    }
+
+    #[test]
+    fn test_include_with_glob() {
+        let dir = std::env::temp_dir().join(format!("sch-ssh-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(
+            dir.join("conf.d/a.conf"),
+            "Host included-a\n    HostName a.example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("conf.d/b.conf"),
+            "Host included-b\n    HostName b.example.com\n    User from-include\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("config"),
+            "Include conf.d/*.conf\n\nHost included-b\n    User from-main\n",
+        )
+        .unwrap();
+
+        let entries = SshConfig::from_file(dir.join("config")).unwrap().entries();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].host, "included-a");
+        assert_eq!(entries[1].host, "included-b");
+        // Included file comes first, so its User wins
+        assert_eq!(entries[1].user.as_deref(), Some("from-include"));
+    }
 }
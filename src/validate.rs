@@ -0,0 +1,736 @@
+use crate::config::{AppConfig, AuthConfig, ConnectionConfig, ReconnectionConfig};
+use crate::config_format::ConfigFormat;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item};
+
+/// Placeholder password written by `generate`
+const PASSWORD_PLACEHOLDER: &str = "CHANGE_ME";
+
+/// How serious a validation finding is; only errors make `validate` fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Position of a value in a TOML file (1-based)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+/// A single validation finding
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.location {
+            Some(location) => write!(f, "{}: {}: {}", level, location, self.message),
+            None => write!(f, "{}: {}", level, self.message),
+        }
+    }
+}
+
+/// Run semantic checks on a loaded configuration. `path` is the main config file;
+/// hosts and channels are located through `config.origins`.
+pub fn validate(config: &AppConfig, path: &Path) -> Vec<Diagnostic> {
+    let mut v = Validator {
+        config,
+        main_path: path,
+        sources: SourceIndex::default(),
+        diagnostics: Vec::new(),
+    };
+
+    v.check_hosts();
+    v.check_channels();
+    v.check_port_conflicts();
+    v.check_reconnection();
+    v.check_metrics();
+    v.check_logging();
+    v.check_hooks();
+
+    v.diagnostics
+}
+
+/// Config files (the main one or includes) whose findings carry no `file:line:column`,
+/// because locations are only mapped for TOML
+pub fn files_without_locations(config: &AppConfig, path: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::iter::once(path)
+        .chain(config.origins.hosts.values().map(PathBuf::as_path))
+        .chain(config.origins.channels.values().map(PathBuf::as_path))
+        .filter(|file| ConfigFormat::from_path(file) != ConfigFormat::Toml)
+        .map(Path::to_path_buf)
+        .collect();
+    files.sort();
+    files.dedup();
+    files
+}
+
+struct Validator<'a> {
+    config: &'a AppConfig,
+    main_path: &'a Path,
+    sources: SourceIndex,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, severity: Severity, message: String, location: Option<Location>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            message,
+            location,
+        });
+    }
+
+    fn host_location(&mut self, index: usize, key: &str) -> Option<Location> {
+        let name = &self.config.hosts[index].name;
+        let file = self.config.origins.hosts.get(name)?;
+        let occurrence = occurrence(self.config.hosts.iter().map(|h| &h.name), index);
+        self.sources.locate(file, "hosts", name, occurrence, key)
+    }
+
+    fn channel_location(&mut self, index: usize, key: &str) -> Option<Location> {
+        let name = &self.config.channels[index].name;
+        let file = self.config.origins.channels.get(name)?;
+        let occurrence = occurrence(self.config.channels.iter().map(|c| &c.name), index);
+        self.sources.locate(file, "channels", name, occurrence, key)
+    }
+
+    fn check_hosts(&mut self) {
+        let config = self.config;
+        let mut first_seen: HashMap<&str, usize> = HashMap::new();
+
+        for (index, host) in config.hosts.iter().enumerate() {
+            if let Some(&first) = first_seen.get(host.name.as_str()) {
+                let first_at = describe(self.host_location(first, "name"));
+                let at = self.host_location(index, "name");
+                self.report(
+                    Severity::Error,
+                    format!("Duplicate host name '{}'{}", host.name, first_at),
+                    at,
+                );
+            } else {
+                first_seen.insert(&host.name, index);
+            }
+
+            match &host.auth {
+                AuthConfig::Password { password } if password == PASSWORD_PLACEHOLDER => {
+                    let at = self.host_location(index, "auth.password");
+                    self.report(
+                        Severity::Warning,
+                        format!(
+                            "Host '{}' still uses the generated placeholder password",
+                            host.name
+                        ),
+                        at,
+                    );
+                }
+                AuthConfig::Key { key_path, .. } => {
+                    if let Some((severity, message)) = check_key_file(key_path) {
+                        let at = self.host_location(index, "auth.key_path");
+                        self.report(severity, format!("Host '{}': {}", host.name, message), at);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_channels(&mut self) {
+        let config = self.config;
+        let mut first_seen: HashMap<&str, usize> = HashMap::new();
+
+        for (index, conn) in config.channels.iter().enumerate() {
+            if let Some(&first) = first_seen.get(conn.name.as_str()) {
+                let first_at = describe(self.channel_location(first, "name"));
+                let at = self.channel_location(index, "name");
+                self.report(
+                    Severity::Error,
+                    format!("Duplicate channel name '{}'{}", conn.name, first_at),
+                    at,
+                );
+            } else {
+                first_seen.insert(&conn.name, index);
+            }
+
+            let mut problems = Vec::new();
+            if let Err(e) = config.channel_hosts(conn) {
+                problems.push(e);
+            }
+            match conn.params() {
+                Ok(params) => {
+                    problems.extend(conn.session_count(&params).err());
+                    problems.extend(conn.health_check(&params).err());
+                }
+                Err(e) => problems.push(e),
+            }
+            for problem in problems {
+                let at = self.channel_location(index, problem.key);
+                self.report(Severity::Error, problem.message, at);
+            }
+
+            if binds_locally(conn) && parse_listen_host(&conn.listen_host).is_none() {
+                let at = self.channel_location(index, "listen_host");
+                self.report(
+                    Severity::Error,
+                    format!(
+                        "Channel '{}': invalid listen_host '{}', expected an IP address such as 127.0.0.1 or 0.0.0.0",
+                        conn.name, conn.listen_host
+                    ),
+                    at,
+                );
+            }
+        }
+    }
+
+    /// Two channels may not bind the same local port on overlapping addresses, nor ask
+    /// the same server to bind the same remote port
+    fn check_port_conflicts(&mut self) {
+        let config = self.config;
+        let channels = &config.channels;
+
+        for (index, conn) in channels.iter().enumerate() {
+            let conflict = channels[..index].iter().position(|other| {
+                if binds_locally(conn) && binds_locally(other) {
+                    conn.ports.local_port == other.ports.local_port
+                        && addresses_overlap(&conn.listen_host, &other.listen_host)
+                } else {
+                    conn.channel_type() == "forwarded-tcpip"
+                        && other.channel_type() == "forwarded-tcpip"
+                        && conn.hostname.iter().any(|h| other.hostname.contains(h))
+                        && conn.ports.dest_port == other.ports.dest_port
+                }
+            });
+            let Some(first) = conflict else {
+                continue;
+            };
+
+            let first_at = describe(self.channel_location(first, "ports"));
+            let at = self.channel_location(index, "ports");
+            let message = if binds_locally(conn) {
+                format!(
+                    "Channel '{}': local port {} is already used by channel '{}'{}",
+                    conn.name,
+                    conn.ports.local_port.unwrap_or_default(),
+                    channels[first].name,
+                    first_at
+                )
+            } else {
+                format!(
+                    "Channel '{}': remote port {} on host '{}' is already bound by channel '{}'{}",
+                    conn.name, conn.ports.dest_port, conn.hostname, channels[first].name, first_at
+                )
+            };
+            self.report(Severity::Error, message, at);
+        }
+    }
+
+    fn check_reconnection(&mut self) {
+        let config = self.config;
+        let global = &config.reconnection;
+        let main_path = self.main_path;
+        if global.initial_delay_secs > global.max_delay_secs {
+            let at = self
+                .sources
+                .locate_key(main_path, &["reconnection", "initial_delay_secs"]);
+            self.report_delays("reconnection", global, at);
+        }
+
+        // Overrides only report what they make wrong, not an error already reported above
+        for (index, host) in config.hosts.iter().enumerate() {
+            let Some(host_override) = host.reconnection.as_ref() else {
+                continue;
+            };
+            let effective = global.with_override(Some(host_override));
+            if effective.initial_delay_secs > effective.max_delay_secs
+                && global.initial_delay_secs <= global.max_delay_secs
+            {
+                let at = self.host_location(index, "reconnection");
+                self.report_delays(
+                    &format!("host '{}' reconnection", host.name),
+                    &effective,
+                    at,
+                );
+            }
+        }
+
+        for (index, channel) in config.channels.iter().enumerate() {
+            let Some(channel_override) = channel.reconnection.as_ref() else {
+                continue;
+            };
+            let host_override = config
+                .hosts
+                .iter()
+                .find(|h| h.name == channel.hostname.primary())
+                .and_then(|h| h.reconnection.as_ref());
+            let inherited = global.with_override(host_override);
+            let effective = inherited.with_override(Some(channel_override));
+            if effective.initial_delay_secs > effective.max_delay_secs
+                && inherited.initial_delay_secs <= inherited.max_delay_secs
+            {
+                let at = self.channel_location(index, "reconnection");
+                self.report_delays(
+                    &format!("channel '{}' reconnection", channel.name),
+                    &effective,
+                    at,
+                );
+            }
+        }
+    }
+
+    /// `[metrics] listen` must be `host:port`; anything but loopback exposes the counters
+    fn check_metrics(&mut self) {
+        let Some(metrics) = &self.config.metrics else {
+            return;
+        };
+        let main_path = self.main_path;
+        let at = self.sources.locate_key(main_path, &["metrics", "listen"]);
+        let port = metrics
+            .listen
+            .rsplit_once(':')
+            .map(|(_, port)| port.parse::<u16>());
+        if !matches!(port, Some(Ok(_))) {
+            self.report(
+                Severity::Error,
+                format!(
+                    "metrics: invalid listen address '{}', expected host:port such as 127.0.0.1:9184",
+                    metrics.listen
+                ),
+                at,
+            );
+        } else if let Ok(addr) = metrics.listen.parse::<SocketAddr>() {
+            if !addr.ip().is_loopback() {
+                self.report(
+                    Severity::Warning,
+                    format!(
+                        "metrics: '{}' is reachable from other machines; channel names and traffic counters will be visible to them",
+                        metrics.listen
+                    ),
+                    at,
+                );
+            }
+        }
+    }
+
+    /// `[logging] level` must be a level or filter directives the service can apply
+    fn check_logging(&mut self) {
+        let Some(logging) = &self.config.logging else {
+            return;
+        };
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&logging.level) {
+            let at = self
+                .sources
+                .locate_key(self.main_path, &["logging", "level"]);
+            self.report(
+                Severity::Error,
+                format!("logging: invalid level '{}': {}", logging.level, e),
+                at,
+            );
+        }
+    }
+
+    /// `[hooks] timeout_secs = 0` would kill every hook as soon as it starts
+    fn check_hooks(&mut self) {
+        let Some(hooks) = &self.config.hooks else {
+            return;
+        };
+        if hooks.timeout_secs == 0 {
+            let at = self
+                .sources
+                .locate_key(self.main_path, &["hooks", "timeout_secs"]);
+            self.report(
+                Severity::Error,
+                "hooks: timeout_secs must be at least 1".to_string(),
+                at,
+            );
+        }
+    }
+
+    fn report_delays(&mut self, what: &str, effective: &ReconnectionConfig, at: Option<Location>) {
+        self.report(
+            Severity::Error,
+            format!(
+                "{}: initial_delay_secs ({}) is greater than max_delay_secs ({})",
+                what, effective.initial_delay_secs, effective.max_delay_secs
+            ),
+            at,
+        );
+    }
+}
+
+/// Whether the channel opens a local listener (direct-tcpip and dynamic)
+fn binds_locally(conn: &ConnectionConfig) -> bool {
+    matches!(conn.channel_type(), "direct-tcpip" | "dynamic")
+}
+
+fn parse_listen_host(listen_host: &str) -> Option<IpAddr> {
+    if listen_host == "localhost" {
+        return Some(IpAddr::from([127, 0, 0, 1]));
+    }
+    listen_host.parse().ok()
+}
+
+/// Whether binding the same port on both addresses would collide
+fn addresses_overlap(a: &str, b: &str) -> bool {
+    match (parse_listen_host(a), parse_listen_host(b)) {
+        (Some(a), Some(b)) => a == b || a.is_unspecified() || b.is_unspecified(),
+        _ => a == b,
+    }
+}
+
+/// Check that a private key exists, is readable and not accessible by others
+fn check_key_file(key_path: &Path) -> Option<(Severity, String)> {
+    let path = match crate::ssh_config::expand_tilde(key_path) {
+        Ok(path) => path,
+        Err(e) => return Some((Severity::Error, e.to_string())),
+    };
+
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return Some((
+                Severity::Error,
+                format!("key file {} is not accessible: {}", path.display(), e),
+            ))
+        }
+    };
+    if !metadata.is_file() {
+        return Some((
+            Severity::Error,
+            format!("key file {} is not a regular file", path.display()),
+        ));
+    }
+    if let Err(e) = std::fs::File::open(&path) {
+        return Some((
+            Severity::Error,
+            format!("key file {} is not readable: {}", path.display(), e),
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Some((
+                Severity::Warning,
+                format!(
+                    "key file {} has permissions {:o}; it should only be accessible by its owner (e.g. 600)",
+                    path.display(),
+                    mode
+                ),
+            ));
+        }
+    }
+
+    None
+}
+
+/// Index of this entry among entries with the same name
+fn occurrence<'a>(names: impl Iterator<Item = &'a String>, index: usize) -> usize {
+    let names: Vec<&String> = names.collect();
+    names[..index]
+        .iter()
+        .filter(|n| **n == names[index])
+        .count()
+}
+
+/// " (first defined at file:line:col)" when the location is known
+fn describe(location: Option<Location>) -> String {
+    location
+        .map(|l| format!(" (first defined at {})", l))
+        .unwrap_or_default()
+}
+
+/// Lazily parsed source files, used to map config entries back to line/column.
+/// Only TOML files are indexed; YAML and JSON findings carry no location.
+#[derive(Default)]
+struct SourceIndex {
+    documents: HashMap<PathBuf, Option<ImDocument<String>>>,
+}
+
+impl SourceIndex {
+    fn document(&mut self, file: &Path) -> Option<&ImDocument<String>> {
+        self.documents
+            .entry(file.to_path_buf())
+            .or_insert_with(|| {
+                if ConfigFormat::from_path(file) != ConfigFormat::Toml {
+                    return None;
+                }
+                let content = std::fs::read_to_string(file).ok()?;
+                ImDocument::parse(content).ok()
+            })
+            .as_ref()
+    }
+
+    /// Locate `key` (dotted, e.g. "auth.key_path") in the `occurrence`-th entry of the
+    /// `array` table array whose `name` is `name`; falls back to the entry itself
+    fn locate(
+        &mut self,
+        file: &Path,
+        array: &str,
+        name: &str,
+        occurrence: usize,
+        key: &str,
+    ) -> Option<Location> {
+        let doc = self.document(file)?;
+        let table = doc
+            .get(array)?
+            .as_array_of_tables()?
+            .iter()
+            .filter(|t| t.get("name").and_then(Item::as_str) == Some(name))
+            .nth(occurrence)?;
+
+        let mut item: Option<&Item> = None;
+        let mut current = Some(table as &dyn toml_edit::TableLike);
+        for part in key.split('.') {
+            item = current.and_then(|t| t.get(part));
+            current = item.and_then(Item::as_table_like);
+        }
+
+        let span = item
+            .and_then(Item::span)
+            .or_else(|| table.get("name").and_then(Item::span))?;
+        Some(location(file, doc.raw(), span.start))
+    }
+
+    /// Locate a value by its key path from the document root
+    fn locate_key(&mut self, file: &Path, keys: &[&str]) -> Option<Location> {
+        let doc = self.document(file)?;
+        let mut item = doc.as_item();
+        for key in keys {
+            item = item.as_table_like()?.get(key)?;
+        }
+        Some(location(file, doc.raw(), item.span()?.start))
+    }
+}
+
+fn location(file: &Path, raw: &str, offset: usize) -> Location {
+    let before = &raw[..offset.min(raw.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Location {
+        file: file.to_path_buf(),
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sch-validate-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn run(name: &str, content: &str) -> Vec<Diagnostic> {
+        let path = write_config(name, content);
+        let config = AppConfig::from_file(&path).unwrap();
+        let diagnostics = validate(&config, &path);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        diagnostics
+    }
+
+    #[test]
+    fn test_valid_config_has_no_diagnostics() {
+        let diagnostics = run(
+            "valid",
+            r#"
+[[hosts]]
+name = "h"
+host = "example.com"
+username = "u"
+auth = { type = "password", password = "secret" }
+
+[[channels]]
+name = "a"
+hostname = "h"
+ports = "8080:80"
+
+[[channels]]
+name = "b"
+hostname = "h"
+ports = "8080:80"
+listen_host = "127.0.0.2"
+"#,
+        );
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_yaml_findings_have_no_location() {
+        let dir = std::env::temp_dir().join(format!("sch-validate-{}-yaml", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        std::fs::write(
+            &path,
+            "hosts:\n  - name: h\n    host: example.com\n    username: u\n    auth: { type: password, password: secret }\nchannels:\n  - name: a\n    hostname: missing\n    ports: \"8080:80\"\n",
+        )
+        .unwrap();
+        let config = AppConfig::from_file(&path).unwrap();
+        let diagnostics = validate(&config, &path);
+        let unlocated = files_without_locations(&config, &path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert!(diagnostics[0]
+            .message
+            .contains("references unknown host 'missing'"));
+        assert_eq!(diagnostics[0].location, None);
+        assert_eq!(unlocated, vec![path]);
+    }
+
+    #[test]
+    fn test_reports_errors_with_locations() {
+        let diagnostics = run(
+            "errors",
+            r#"[[hosts]]
+name = "h"
+host = "example.com"
+username = "u"
+auth = { type = "key", key_path = "/nonexistent/sch/key" }
+
+[[channels]]
+name = "a"
+hostname = "h"
+ports = "8080:80"
+
+[[channels]]
+name = "a"
+hostname = "missing"
+ports = "8080:81"
+listen_host = "0.0.0.0"
+
+[[channels]]
+name = "c"
+hostname = "h"
+ports = "9000:80"
+listen_host = "not-an-ip"
+
+[reconnection]
+initial_delay_secs = 60
+max_delay_secs = 30
+"#,
+        );
+        let lines: Vec<String> = diagnostics
+            .iter()
+            .map(|d| {
+                let at = d.location.as_ref().map(|l| (l.line, l.column));
+                format!("{:?} {:?} {}", d.severity, at, d.message)
+            })
+            .collect();
+
+        let expect = |needle: &str, line: usize| {
+            assert!(
+                lines
+                    .iter()
+                    .any(|l| l.contains(needle) && l.contains(&format!("Some(({},", line))),
+                "missing '{}' at line {} in {:#?}",
+                needle,
+                line,
+                lines
+            );
+        };
+        expect("key file /nonexistent/sch/key is not accessible", 5);
+        expect("Duplicate channel name 'a' (first defined at", 13);
+        expect("references unknown host 'missing'", 14);
+        expect("local port 8080 is already used by channel 'a'", 15);
+        expect("invalid listen_host 'not-an-ip'", 22);
+        expect(
+            "initial_delay_secs (60) is greater than max_delay_secs (30)",
+            25,
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_reports_invalid_reconnection_override() {
+        let diagnostics = run(
+            "override",
+            r#"[[hosts]]
+name = "h"
+host = "example.com"
+username = "u"
+auth = { type = "password", password = "secret" }
+reconnection = { initial_delay_secs = 10 }
+
+[[channels]]
+name = "a"
+hostname = "h"
+ports = "8080:80"
+reconnection = { max_delay_secs = 5 }
+"#,
+        );
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert!(diagnostics[0]
+            .message
+            .contains("channel 'a' reconnection: initial_delay_secs (10)"));
+        assert_eq!(diagnostics[0].location.as_ref().unwrap().line, 12);
+    }
+
+    #[test]
+    fn test_reports_invalid_health_check() {
+        let diagnostics = run(
+            "health",
+            r#"[[hosts]]
+name = "h"
+host = "example.com"
+username = "u"
+auth = { type = "password", password = "secret" }
+
+[[channels]]
+name = "web"
+hostname = "h"
+ports = "8080:80"
+health_check = { probe = "http", expect_status = 204 }
+
+[[channels]]
+name = "proxy"
+hostname = "h"
+channel_type = "dynamic"
+ports = "1080"
+health_check = { probe = "tcp" }
+
+[[channels]]
+name = "callback"
+hostname = "h"
+channel_type = "forwarded-tcpip"
+ports = "3000:9000"
+health_check = { probe = "tcp" }
+"#,
+        );
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert!(diagnostics[0]
+            .message
+            .contains("Channel 'proxy': health_check needs a target"));
+        assert_eq!(diagnostics[0].location.as_ref().unwrap().line, 18);
+        assert!(diagnostics[1]
+            .message
+            .contains("Channel 'callback': health_check on forwarded-tcpip needs a probe"));
+        assert_eq!(diagnostics[1].location.as_ref().unwrap().line, 25);
+    }
+}
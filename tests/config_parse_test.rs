@@ -1,6 +1,8 @@
 // Test to verify TOML parsing supports different auth per channel (via host reference)
 
-use ssh_channels_hub::config::AppConfig;
+use ssh_channels_hub::config::{
+    AppConfig, AuthFailurePolicy, BackoffStrategy, BalanceStrategy, HealthProbe,
+};
 
 #[test]
 fn test_multiple_channels_different_auth() {
@@ -69,8 +71,8 @@ ports = "8082:80"
     // Channel 1: Password authentication (from host-password)
     let ch1 = &channels[0];
     assert_eq!(ch1.name, "test-password");
-    assert_eq!(ch1.username, "user1");
-    match &ch1.auth {
+    assert_eq!(ch1.hosts[0].username, "user1");
+    match &ch1.hosts[0].auth {
         ssh_channels_hub::config::AuthConfig::Password { password } => {
             assert_eq!(password, "test-password-123");
         }
@@ -80,8 +82,8 @@ ports = "8082:80"
     // Channel 2: Key authentication with default key
     let ch2 = &channels[1];
     assert_eq!(ch2.name, "test-key-default");
-    assert_eq!(ch2.username, "user2");
-    match &ch2.auth {
+    assert_eq!(ch2.hosts[0].username, "user2");
+    match &ch2.hosts[0].auth {
         ssh_channels_hub::config::AuthConfig::Key {
             key_path,
             passphrase,
@@ -95,8 +97,8 @@ ports = "8082:80"
     // Channel 3: Key authentication with different key
     let ch3 = &channels[2];
     assert_eq!(ch3.name, "test-key-custom");
-    assert_eq!(ch3.username, "user3");
-    match &ch3.auth {
+    assert_eq!(ch3.hosts[0].username, "user3");
+    match &ch3.hosts[0].auth {
         ssh_channels_hub::config::AuthConfig::Key {
             key_path,
             passphrase,
@@ -110,7 +112,7 @@ ports = "8082:80"
     if let (
         ssh_channels_hub::config::AuthConfig::Key { key_path: k2, .. },
         ssh_channels_hub::config::AuthConfig::Key { key_path: k3, .. },
-    ) = (&ch2.auth, &ch3.auth)
+    ) = (&ch2.hosts[0].auth, &ch3.hosts[0].auth)
     {
         assert_ne!(k2, k3, "Channels 2 and 3 should use different keys");
     }
@@ -133,10 +135,713 @@ fn test_load_config_from_file() {
 
         let channels = config.build_channels().expect("build_channels");
         for channel in &channels {
-            match &channel.auth {
+            match &channel.hosts[0].auth {
                 ssh_channels_hub::config::AuthConfig::Password { .. } => {}
                 ssh_channels_hub::config::AuthConfig::Key { .. } => {}
             }
         }
     }
 }
+
+#[test]
+fn test_generate_channels_from_ssh_forwards() {
+    use ssh_channels_hub::ssh_config::SshConfig;
+
+    let path = std::env::temp_dir().join(format!("ssh-forwards-{}.conf", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+Host bastion
+    HostName bastion.example.com
+    User ops
+    IdentityFile /keys/ops
+    LocalForward 5432 db.internal:5432
+    LocalForward *:8080 web.internal:80
+    RemoteForward 9000 127.0.0.1:3000
+    DynamicForward 1080
+"#,
+    )
+    .expect("write ssh config");
+    let ssh_config = SshConfig::from_file(&path).expect("parse ssh config");
+    std::fs::remove_file(&path).expect("remove ssh config");
+
+    let config = AppConfig::from_ssh_config_entries(ssh_config.entries());
+    assert_eq!(config.hosts.len(), 1);
+
+    let names: Vec<&str> = config.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "bastion-L5432",
+            "bastion-L8080",
+            "bastion-R9000",
+            "bastion-D1080"
+        ]
+    );
+    assert_eq!(config.channels[1].listen_host, "0.0.0.0");
+    assert_eq!(config.channels[1].dest_host, "web.internal");
+
+    let channels = config.build_channels().expect("build_channels");
+    match &channels[2].params {
+        ssh_channels_hub::config::ChannelTypeParams::ForwardedTcpIp {
+            remote_bind_port,
+            local_connect_port,
+            ..
+        } => {
+            assert_eq!(*remote_bind_port, 9000);
+            assert_eq!(*local_connect_port, 3000);
+        }
+        _ => panic!("RemoteForward should become a forwarded-tcpip channel"),
+    }
+    match &channels[3].params {
+        ssh_channels_hub::config::ChannelTypeParams::Dynamic { local_port, .. } => {
+            assert_eq!(*local_port, 1080);
+        }
+        _ => panic!("DynamicForward should become a dynamic channel"),
+    }
+
+    // Generated config round-trips through TOML
+    let text = toml::to_string_pretty(&config).expect("serialize");
+    let reparsed: AppConfig = toml::from_str(&text).expect("reparse");
+    assert_eq!(reparsed.channels.len(), 4);
+    assert_eq!(reparsed.channels[3].ports.dest_port, 0);
+}
+
+#[test]
+fn test_channels_resolve_hosts_from_ssh_config() {
+    let dir = std::env::temp_dir().join(format!("sch-ssh-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("ssh_config"),
+        r#"
+Host prod-bastion
+    HostName bastion.example.com
+    User ops
+    Port 2222
+    IdentityFile /keys/ops
+
+Host legacy
+    HostName legacy.example.com
+    User root
+    IdentityFile /keys/legacy
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("configs.toml"),
+        r#"
+ssh_config = "ssh_config"
+
+[[channels]]
+name = "explicit"
+hostname = "ssh:prod-bastion"
+ports = "5432:5432"
+
+[[channels]]
+name = "implicit"
+hostname = "legacy"
+ports = "8080:80"
+"#,
+    )
+    .unwrap();
+
+    let config = AppConfig::from_file(dir.join("configs.toml")).expect("load config");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let channels = config.build_channels().expect("build_channels");
+    assert_eq!(channels[0].hosts[0].host, "bastion.example.com");
+    assert_eq!(channels[0].hosts[0].port, 2222);
+    assert_eq!(channels[0].hosts[0].username, "ops");
+    assert_eq!(channels[1].hosts[0].host, "legacy.example.com");
+    assert_eq!(channels[1].hosts[0].username, "root");
+}
+
+#[test]
+fn test_identities_only_never_falls_back_to_default_keys() {
+    let dir = std::env::temp_dir().join(format!("sch-identities-only-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("ssh_config"),
+        r#"
+Host strict
+    HostName strict.example.com
+    User ops
+    IdentitiesOnly yes
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("configs.toml"),
+        r#"
+ssh_config = "ssh_config"
+
+[[channels]]
+name = "db"
+hostname = "strict"
+ports = "5432:5432"
+"#,
+    )
+    .unwrap();
+
+    let err = AppConfig::from_file(dir.join("configs.toml")).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        err.to_string()
+            .contains("sets IdentitiesOnly yes but has no IdentityFile"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_include_merges_hosts_and_channels() {
+    let dir = std::env::temp_dir().join(format!("sch-include-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+    std::fs::write(
+        dir.join("configs.toml"),
+        r#"
+include = ["conf.d/*.toml", "missing-personal.toml"]
+
+[[hosts]]
+name = "shared"
+host = "shared.example.com"
+username = "team"
+
+[hosts.auth]
+type = "password"
+password = "x"
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("conf.d/10-db.toml"),
+        r#"
+[[channels]]
+name = "db"
+hostname = "shared"
+ports = "5432:5432"
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("conf.d/20-web.toml"),
+        r#"
+[[hosts]]
+name = "web"
+host = "web.example.com"
+username = "me"
+
+[hosts.auth]
+type = "password"
+password = "y"
+
+[[channels]]
+name = "web"
+hostname = "web"
+ports = "8080:80"
+"#,
+    )
+    .unwrap();
+
+    let config = AppConfig::from_file(dir.join("configs.toml")).expect("load config");
+    assert_eq!(config.hosts.len(), 2);
+    assert_eq!(config.channels.len(), 2);
+    assert!(config.origins.channels["db"].ends_with("conf.d/10-db.toml"));
+    assert!(config.origins.hosts["shared"].ends_with("configs.toml"));
+    assert_eq!(config.build_channels().expect("build_channels").len(), 2);
+
+    // Same channel name in two files is rejected
+    std::fs::write(
+        dir.join("conf.d/30-dup.toml"),
+        r#"
+[[channels]]
+name = "db"
+hostname = "web"
+ports = "5433:5432"
+"#,
+    )
+    .unwrap();
+    let err = AppConfig::from_file(dir.join("configs.toml")).unwrap_err();
+    assert!(err.to_string().contains("Duplicate channel 'db'"));
+
+    // ...and so is the same name twice within one included file
+    std::fs::write(
+        dir.join("conf.d/30-dup.toml"),
+        r#"
+[[channels]]
+name = "api"
+hostname = "web"
+ports = "9000:80"
+
+[[channels]]
+name = "api"
+hostname = "web"
+ports = "9001:80"
+"#,
+    )
+    .unwrap();
+    let err = AppConfig::from_file(dir.join("configs.toml")).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        err.to_string()
+            .contains("Duplicate channel 'api' defined twice in"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_vars_interpolation_on_load() {
+    let dir = std::env::temp_dir().join(format!("sch-vars-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("configs.toml"),
+        r#"
+[vars]
+deploy_user = "deploy"
+key_dir = "/keys"
+
+[[hosts]]
+name = "prod"
+host = "${SCH_TEST_UNSET_HOST:-prod.example.com}"
+username = "${deploy_user}"
+
+[hosts.auth]
+type = "key"
+key_path = "${key_dir}/${deploy_user}"
+passphrase = "secret"
+
+[[channels]]
+name = "db"
+hostname = "prod"
+ports = "${SCH_TEST_UNSET_PORT:-15432}:5432"
+"#,
+    )
+    .unwrap();
+
+    let config = AppConfig::from_file(dir.join("configs.toml")).expect("load config");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(config.hosts[0].host, "prod.example.com");
+    assert_eq!(config.hosts[0].username, "deploy");
+    assert_eq!(config.channels[0].ports.local_port, Some(15432));
+    match &config.hosts[0].auth {
+        ssh_channels_hub::config::AuthConfig::Key { key_path, .. } => {
+            assert_eq!(key_path.to_string_lossy(), "/keys/deploy");
+        }
+        _ => panic!("expected key auth"),
+    }
+    match &config.redacted().hosts[0].auth {
+        ssh_channels_hub::config::AuthConfig::Key { passphrase, .. } => {
+            assert_eq!(passphrase.as_deref(), Some("********"));
+        }
+        _ => panic!("expected key auth"),
+    }
+}
+
+#[test]
+fn test_json_schema_matches_checked_in_copy() {
+    let schema = serde_json::to_value(AppConfig::json_schema()).unwrap();
+
+    let ports = &schema["definitions"]["PortForward"];
+    assert_eq!(ports["type"], serde_json::json!(["string", "integer"]));
+    assert!(ports["pattern"].is_string());
+    let channel_types = &schema["definitions"]["ConnectionConfig"]["properties"]["channel_type"];
+    assert_eq!(
+        channel_types["enum"],
+        serde_json::json!(["direct-tcpip", "forwarded-tcpip", "dynamic", "session"])
+    );
+    for name in ["HostConfig", "AuthConfig", "ReconnectionConfig"] {
+        assert!(schema["definitions"][name].is_object(), "missing {}", name);
+    }
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/config.schema.json");
+    let checked_in: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(
+        schema, checked_in,
+        "docs/config.schema.json is stale; run `ssh-channels-hub schema -o docs/config.schema.json`"
+    );
+}
+
+#[test]
+fn test_yaml_and_json_configs_load() {
+    let dir = std::env::temp_dir().join(format!("sch-formats-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let yaml = dir.join("config.yaml");
+    std::fs::write(
+        &yaml,
+        r#"
+vars:
+  user: deploy
+hosts:
+  - name: h
+    host: example.com
+    username: ${user}
+    auth:
+      type: password
+      password: secret
+channels:
+  - name: socks
+    hostname: h
+    channel_type: dynamic
+    ports: 1080
+  - name: web
+    hostname: h
+    ports: "8080:80"
+"#,
+    )
+    .unwrap();
+
+    let json = dir.join("config.json");
+    std::fs::write(
+        &json,
+        r#"{
+  "hosts": [{"name": "h", "host": "example.com", "username": "u",
+             "auth": {"type": "key", "key_path": "/k", "passphrase": null}}],
+  "channels": [{"name": "web", "hostname": "h", "ports": "8080:80"}],
+  "reconnection": {"max_retries": 3}
+}"#,
+    )
+    .unwrap();
+
+    let from_yaml = AppConfig::from_file(&yaml).unwrap();
+    let from_json = AppConfig::from_file(&json).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(from_yaml.hosts[0].username, "deploy");
+    assert_eq!(from_yaml.channels[0].ports.local_port, Some(1080));
+    assert_eq!(from_yaml.channels[0].ports.dest_port, 0);
+    assert_eq!(from_yaml.channels[1].ports.dest_port, 80);
+    assert_eq!(from_yaml.build_channels().unwrap().len(), 2);
+
+    assert_eq!(from_json.reconnection.max_retries, 3);
+    assert_eq!(from_json.channels[0].ports.local_port, Some(8080));
+}
+
+#[test]
+fn test_legacy_layout_is_migrated_on_load() {
+    let dir = std::env::temp_dir().join(format!("sch-legacy-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(
+        &path,
+        r#"
+[[channels]]
+name = "web"
+host = "example.com"
+username = "user"
+ports = "8080:80"
+auth = { type = "password", password = "secret" }
+"#,
+    )
+    .unwrap();
+
+    let config = AppConfig::from_file(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(config.version, 1);
+    assert_eq!(config.hosts.len(), 1);
+    assert_eq!(config.hosts[0].host, "example.com");
+    assert_eq!(config.channels[0].hostname, config.hosts[0].name);
+}
+
+#[test]
+fn test_reconnection_overrides_resolve_per_channel() {
+    let toml_content = r#"
+[reconnection]
+max_retries = 5
+initial_delay_secs = 2
+max_delay_secs = 60
+
+[[hosts]]
+name = "flaky"
+host = "flaky.example.com"
+username = "u"
+auth = { type = "password", password = "p" }
+reconnection = { max_retries = 0, jitter = true, backoff = "decorrelated-jitter" }
+
+[[hosts]]
+name = "stable"
+host = "stable.example.com"
+username = "u"
+auth = { type = "password", password = "p" }
+keepalive_interval_secs = 0
+
+[[channels]]
+name = "flaky-web"
+hostname = "flaky"
+ports = "8080:80"
+
+[[channels]]
+name = "flaky-db"
+hostname = "flaky"
+ports = "5432:5432"
+reconnection = { max_delay_secs = 10, auth_failure = "stop", client_grace_secs = 0 }
+
+[[channels]]
+name = "stable-web"
+hostname = "stable"
+ports = "8081:80"
+"#;
+
+    let config: AppConfig = toml::from_str(toml_content).expect("parse");
+    let channels = config.build_channels().expect("build_channels");
+    let policy = |name: &str| {
+        channels
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.reconnection.clone())
+            .unwrap()
+    };
+
+    let web = policy("flaky-web");
+    assert_eq!(web.max_retries, 0);
+    assert!(web.jitter);
+    assert_eq!(web.max_delay_secs, 60);
+    assert_eq!(web.strategy(), BackoffStrategy::DecorrelatedJitter);
+
+    let keepalive = |name: &str| {
+        let c = channels.iter().find(|c| c.name == name).unwrap();
+        (c.hosts[0].keepalive_interval, c.hosts[0].keepalive_max)
+    };
+    assert_eq!(
+        keepalive("flaky-web"),
+        (Some(std::time::Duration::from_secs(15)), 3)
+    );
+    assert_eq!(keepalive("stable-web"), (None, 3));
+
+    let db = policy("flaky-db");
+    assert_eq!(db.max_retries, 0);
+    assert!(db.jitter);
+    assert_eq!(db.max_delay_secs, 10);
+    assert_eq!(db.auth_failure, AuthFailurePolicy::Stop);
+    assert_eq!(db.client_grace_secs, 0);
+    assert_eq!(web.client_grace_secs, 10);
+
+    let stable = policy("stable-web");
+    assert_eq!(stable.max_retries, 5);
+    assert!(!stable.jitter);
+    assert_eq!(stable.strategy(), BackoffStrategy::Exponential);
+    assert_eq!(stable.reset_after_secs, 60);
+    assert_eq!(stable.auth_failure, AuthFailurePolicy::Backoff);
+    assert_eq!(stable.auth_failure_delay_secs, 300);
+}
+
+#[test]
+fn test_hostname_list_resolves_failover_candidates() {
+    let toml_content = r#"
+[[hosts]]
+name = "bastion-eu"
+host = "eu.example.com"
+username = "u"
+auth = { type = "password", password = "p" }
+reconnection = { max_delay_secs = 120 }
+
+[[hosts]]
+name = "bastion-us"
+host = "us.example.com"
+port = 2222
+username = "u"
+auth = { type = "password", password = "p" }
+
+[[channels]]
+name = "db"
+hostname = ["bastion-eu", "bastion-us"]
+ports = "5432:5432"
+reconnection = { failback_interval_secs = 30 }
+
+[[channels]]
+name = "web"
+hostname = "bastion-us"
+ports = "8080:80"
+"#;
+
+    let config: AppConfig = toml::from_str(toml_content).expect("parse");
+    assert!(config.channels[0].hostname.has_fallbacks());
+    assert_eq!(config.channels[0].hostname.primary(), "bastion-eu");
+    assert!(!config.channels[1].hostname.has_fallbacks());
+
+    let channels = config.build_channels().expect("build_channels");
+    let hosts: Vec<_> = channels[0]
+        .hosts
+        .iter()
+        .map(|h| (h.name.as_str(), h.host.as_str(), h.port))
+        .collect();
+    assert_eq!(
+        hosts,
+        vec![
+            ("bastion-eu", "eu.example.com", 22),
+            ("bastion-us", "us.example.com", 2222)
+        ]
+    );
+    // Host-level overrides come from the preferred host
+    assert_eq!(channels[0].reconnection.max_delay_secs, 120);
+    assert_eq!(channels[0].reconnection.failback_interval_secs, 30);
+    assert_eq!(channels[1].reconnection.failback_interval_secs, 0);
+
+    // A single host is still written as a plain string
+    let written = toml::to_string(&config).expect("serialize");
+    assert!(written.contains(r#"hostname = ["bastion-eu", "bastion-us"]"#));
+    assert!(written.contains(r#"hostname = "bastion-us""#));
+
+    let empty = toml_content.replace(r#"["bastion-eu", "bastion-us"]"#, "[]");
+    assert!(toml::from_str::<AppConfig>(&empty).is_err());
+
+    let unknown = toml_content.replace(r#""bastion-us"]"#, r#""bastion-ap"]"#);
+    let config: AppConfig = toml::from_str(&unknown).expect("parse");
+    assert!(config.build_channels().is_err());
+}
+
+#[test]
+fn test_sessions_and_balance() {
+    let toml_content = r#"
+[[hosts]]
+name = "db"
+host = "db.example.com"
+username = "u"
+auth = { type = "password", password = "p" }
+
+[[channels]]
+name = "export"
+hostname = "db"
+ports = "5432:5432"
+sessions = 4
+balance = "least-connections"
+
+[[channels]]
+name = "web"
+hostname = "db"
+ports = "8080:80"
+"#;
+
+    let config: AppConfig = toml::from_str(toml_content).expect("parse");
+    let channels = config.build_channels().expect("build_channels");
+    assert_eq!(channels[0].sessions, 4);
+    assert_eq!(channels[0].balance, BalanceStrategy::LeastConnections);
+    assert_eq!(channels[1].sessions, 1);
+    assert_eq!(channels[1].balance, BalanceStrategy::RoundRobin);
+
+    // Several sessions would ask the server to bind the same remote port more than once
+    let remote = toml_content.replace(
+        "sessions = 4",
+        "sessions = 4\nchannel_type = \"forwarded-tcpip\"",
+    );
+    let config: AppConfig = toml::from_str(&remote).expect("parse");
+    assert!(config.build_channels().is_err());
+
+    let zero = toml_content.replace("sessions = 4", "sessions = 0");
+    let config: AppConfig = toml::from_str(&zero).expect("parse");
+    assert!(config.build_channels().is_err());
+}
+
+#[test]
+fn test_health_check_resolves_probe_target() {
+    let toml_content = r#"
+[[hosts]]
+name = "bastion"
+host = "bastion.example.com"
+username = "u"
+auth = { type = "password", password = "p" }
+
+[[channels]]
+name = "api"
+hostname = "bastion"
+ports = "8080:80"
+dest_host = "api.internal"
+health_check = { probe = "http", path = "/healthz", reconnect_after = 3 }
+
+[[channels]]
+name = "callback"
+hostname = "bastion"
+channel_type = "forwarded-tcpip"
+ports = "3000:9000"
+health_check = { probe = "redis", interval_secs = 10 }
+
+[[channels]]
+name = "proxy"
+hostname = "bastion"
+channel_type = "dynamic"
+ports = "1080"
+health_check = { probe = "banner", expect = "SSH-", target = "git.internal:22" }
+"#;
+
+    let config: AppConfig = toml::from_str(toml_content).expect("parse");
+    let channels = config.build_channels().expect("build_channels");
+
+    let api = channels[0].health_check.as_ref().expect("api health check");
+    assert_eq!(
+        api.probe,
+        HealthProbe::Http {
+            path: "/healthz".to_string(),
+            expect_status: 200
+        }
+    );
+    assert_eq!((api.host.as_str(), api.port), ("api.internal", 80));
+    assert_eq!(api.interval.as_secs(), 30);
+    assert_eq!(api.timeout.as_secs(), 5);
+    assert_eq!(api.reconnect_after, 3);
+
+    // Remote forwards probe the port bound on the server, which hands it back to the local target
+    let callback = channels[1]
+        .health_check
+        .as_ref()
+        .expect("callback health check");
+    assert_eq!((callback.host.as_str(), callback.port), ("127.0.0.1", 9000));
+    assert_eq!(callback.reconnect_after, 0);
+
+    let proxy = channels[2]
+        .health_check
+        .as_ref()
+        .expect("proxy health check");
+    assert_eq!((proxy.host.as_str(), proxy.port), ("git.internal", 22));
+
+    // Dynamic channels have no destination of their own
+    let untargeted = toml_content.replace(r#", target = "git.internal:22""#, "");
+    let config: AppConfig = toml::from_str(&untargeted).expect("parse");
+    assert!(config.build_channels().is_err());
+
+    let bad_target = toml_content.replace("git.internal:22", "git.internal");
+    let config: AppConfig = toml::from_str(&bad_target).expect("parse");
+    assert!(config.build_channels().is_err());
+
+    // The server accepts that connection itself, so tcp would pass with the local target down
+    let tcp_callback = toml_content.replace(r#"probe = "redis""#, r#"probe = "tcp""#);
+    let config: AppConfig = toml::from_str(&tcp_callback).expect("parse");
+    let err = config.build_channels().unwrap_err().to_string();
+    assert!(err.contains("tcp only reaches the SSH server"), "{}", err);
+
+    let unknown_probe = toml_content.replace(r#"probe = "redis""#, r#"probe = "ftp""#);
+    assert!(toml::from_str::<AppConfig>(&unknown_probe).is_err());
+}
+
+#[test]
+fn test_generated_channel_names_are_unique() {
+    use ssh_channels_hub::ssh_config::SshConfig;
+
+    let path = std::env::temp_dir().join(format!("ssh-dup-forwards-{}.conf", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+Host bastion
+    HostName bastion.example.com
+    User ops
+    IdentityFile /keys/ops
+    LocalForward 127.0.0.1:8080 web.internal:80
+    LocalForward 192.168.1.10:8080 admin.internal:80
+    LocalForward 8080 api.internal:80
+"#,
+    )
+    .expect("write ssh config");
+    let ssh_config = SshConfig::from_file(&path).expect("parse ssh config");
+    std::fs::remove_file(&path).expect("remove ssh config");
+
+    let config = AppConfig::from_ssh_config_entries(ssh_config.entries());
+    let names: Vec<&str> = config.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["bastion-L8080", "bastion-L8080-2", "bastion-L8080-3"]
+    );
+    assert_eq!(config.channels[1].dest_host, "admin.internal");
+}
@@ -0,0 +1,278 @@
+// End-to-end tunnel tests against an in-process SSH server that allows direct-tcpip
+// channels and remote forwards on 127.0.0.1
+
+use russh::server::{self, Auth, Msg, Session};
+use russh::Channel;
+use ssh_channels_hub::config::{AppConfig, ChannelConfig};
+use ssh_channels_hub::ssh::{self, ChannelState, ChannelStatus, SshManager};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+struct TestServer;
+
+#[async_trait::async_trait]
+impl server::Handler for TestServer {
+    type Error = russh::Error;
+
+    async fn auth_password(&mut self, _user: &str, _password: &str) -> Result<Auth, Self::Error> {
+        Ok(Auth::Accept)
+    }
+
+    async fn channel_open_direct_tcpip(
+        &mut self,
+        channel: Channel<Msg>,
+        host_to_connect: &str,
+        port_to_connect: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        match TcpStream::connect((host_to_connect, port_to_connect as u16)).await {
+            Ok(stream) => {
+                tokio::spawn(bridge(channel, stream));
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    async fn tcpip_forward(
+        &mut self,
+        address: &str,
+        port: &mut u32,
+        session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        let Ok(listener) = TcpListener::bind(("127.0.0.1", *port as u16)).await else {
+            return Ok(false);
+        };
+        let handle = session.handle();
+        let (address, port) = (address.to_string(), *port);
+        tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let opened = handle
+                    .channel_open_forwarded_tcpip(
+                        address.clone(),
+                        port,
+                        peer.ip().to_string(),
+                        peer.port() as u32,
+                    )
+                    .await;
+                match opened {
+                    Ok(channel) => drop(tokio::spawn(bridge(channel, stream))),
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(true)
+    }
+}
+
+async fn bridge(channel: Channel<Msg>, mut stream: TcpStream) {
+    let mut channel = channel.into_stream();
+    let _ = tokio::io::copy_bidirectional(&mut channel, &mut stream).await;
+}
+
+/// Start the SSH server on a free port and return the port
+async fn start_server() -> u16 {
+    let config = Arc::new(server::Config {
+        keys: vec![russh_keys::key::KeyPair::generate_ed25519().unwrap()],
+        ..Default::default()
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let config = Arc::clone(&config);
+            tokio::spawn(async move {
+                if let Ok(session) = server::run_stream(config, socket, TestServer).await {
+                    let _ = session.await;
+                }
+            });
+        }
+    });
+    port
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// A local service that accepts clients and waits for them to speak first
+async fn start_silent_service() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut clients = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            clients.push(stream);
+        }
+    });
+    port
+}
+
+/// A local service that greets every client with an SSH banner
+async fn start_banner_service() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let _ = stream.write_all(b"SSH-2.0-local\r\n").await;
+        }
+    });
+    port
+}
+
+const BANNER_CHECK: &str =
+    r#"health_check = { probe = "banner", expect = "SSH-", interval_secs = 1, timeout_secs = 3 }"#;
+
+/// Channel config for a remote forward from `remote_port` on the test server to `local_port`,
+/// followed by `extra` channel keys
+fn remote_forward(server_port: u16, local_port: u16, remote_port: u16, extra: &str) -> AppConfig {
+    toml::from_str(&format!(
+        r#"
+[[hosts]]
+name = "test"
+host = "127.0.0.1"
+port = {server_port}
+username = "u"
+auth = {{ type = "password", password = "p" }}
+
+[[channels]]
+name = "callback"
+hostname = "test"
+channel_type = "forwarded-tcpip"
+ports = "{local_port}:{remote_port}"
+{extra}
+"#
+    ))
+    .expect("parse")
+}
+
+/// Start the channel and wait until its status satisfies `ready`
+async fn start_until(
+    config: &AppConfig,
+    ready: impl Fn(&ChannelStatus) -> bool,
+) -> (SshManager, ChannelConfig, ChannelStatus) {
+    let channel = config.build_channels().expect("build_channels").remove(0);
+    let mut manager = SshManager::new(channel.clone());
+    manager.start().await.expect("start");
+    let status = tokio::time::timeout(Duration::from_secs(15), async {
+        loop {
+            let status = manager.status();
+            if ready(&status) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("channel not ready within 15s");
+    (manager, channel, status)
+}
+
+/// Start the channel and wait until its first health check has run
+async fn first_health_check(config: &AppConfig) -> (SshManager, ChannelStatus) {
+    let (manager, _, status) = start_until(config, |s| s.health.is_some()).await;
+    (manager, status)
+}
+
+/// What `test` reports for the remote forward `config` while the service runs it
+async fn test_remote_forward(config: &AppConfig) -> Result<(), String> {
+    let (mut manager, channel, _) =
+        start_until(config, |s| s.state == ChannelState::Connected).await;
+    let result = ssh::check_remote_forward(&channel, &channel.hosts[0]).await;
+    manager.stop().await.unwrap();
+    result
+}
+
+#[tokio::test]
+async fn test_remote_forward_health_check_reaches_local_target() {
+    let server_port = start_server().await;
+    let local_port = start_banner_service().await;
+    let config = remote_forward(server_port, local_port, free_port(), BANNER_CHECK);
+
+    let (mut manager, status) = first_health_check(&config).await;
+    manager.stop().await.unwrap();
+    let health = status.health.unwrap();
+    assert!(health.healthy, "{:?}", health);
+}
+
+#[tokio::test]
+async fn test_remote_forward_health_check_fails_with_local_target_down() {
+    let server_port = start_server().await;
+    // Nothing listens on the local target; the server still accepts on its end
+    let config = remote_forward(server_port, free_port(), free_port(), BANNER_CHECK);
+
+    let (mut manager, status) = first_health_check(&config).await;
+    manager.stop().await.unwrap();
+    let health = status.health.unwrap();
+    assert!(!health.healthy, "{:?}", health);
+    assert_eq!(health.failures, 1);
+    // The service closes the forwarded channel instead of leaving the probe to time out
+    assert_eq!(
+        health.error.as_deref(),
+        Some("connection closed without a reply")
+    );
+}
+
+#[tokio::test]
+async fn test_check_remote_forward_needs_an_answer() {
+    let server_port = start_server().await;
+
+    let banner = remote_forward(server_port, start_banner_service().await, free_port(), "");
+    assert_eq!(test_remote_forward(&banner).await, Ok(()));
+
+    let down = remote_forward(server_port, free_port(), free_port(), "");
+    let err = test_remote_forward(&down).await.unwrap_err();
+    assert!(err.contains("closed without data"), "{}", err);
+
+    // Silence proves nothing: the server holds its end of the forward open by itself
+    let silent = remote_forward(server_port, start_silent_service().await, free_port(), "");
+    let err = test_remote_forward(&silent).await.unwrap_err();
+    assert!(err.contains("no data within"), "{}", err);
+
+    // With a health check, its probe is the confirmation
+    let checked = remote_forward(
+        server_port,
+        start_banner_service().await,
+        free_port(),
+        BANNER_CHECK,
+    );
+    assert_eq!(test_remote_forward(&checked).await, Ok(()));
+    let checked_down = remote_forward(server_port, free_port(), free_port(), BANNER_CHECK);
+    assert!(test_remote_forward(&checked_down).await.is_err());
+}
+
+#[tokio::test]
+async fn test_check_remote_forward_ignores_probes_that_prove_nothing() {
+    let server_port = start_server().await;
+
+    // A tcp probe aimed at the forward only reaches the server, which accepts by itself
+    let remote_port = free_port();
+    let tcp = remote_forward(
+        server_port,
+        free_port(),
+        remote_port,
+        &format!(
+            r#"health_check = {{ probe = "tcp", target = "127.0.0.1:{remote_port}", timeout_secs = 1 }}"#
+        ),
+    );
+    let err = test_remote_forward(&tcp).await.unwrap_err();
+    assert!(err.contains("closed without data"), "{}", err);
+
+    // A probe for another service must not judge this forward
+    let elsewhere = remote_forward(
+        server_port,
+        free_port(),
+        free_port(),
+        r#"health_check = { probe = "banner", expect = "SSH-", target = "git.internal:22", timeout_secs = 1 }"#,
+    );
+    let err = test_remote_forward(&elsewhere).await.unwrap_err();
+    assert!(err.contains("closed without data"), "{}", err);
+}